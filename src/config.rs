@@ -0,0 +1,237 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+const CONFIG_FILE: &str = "config.toml";
+/// Row count for `ls`, `find`, and `grep` when neither a flag nor config sets one.
+const DEFAULT_LIMIT: usize = 20;
+/// Handoff window when neither `--window` nor config sets one.
+const DEFAULT_WINDOW: usize = 10;
+
+/// Settings from `config.toml`. Every key is optional; unset keys fall back to built-in
+/// behavior.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Write commands fail instead of creating a missing `.crumbs` store.
+    pub require_store: Option<bool>,
+    /// Maximum memory text length, in grapheme clusters (default 100).
+    pub max_len: Option<usize>,
+    /// Refuse to record memory text that looks like a secret, as with `--detect-secret`.
+    pub detect_secrets: Option<bool>,
+    /// Default row count for `cr ls` (default 20).
+    pub ls_limit: Option<usize>,
+    /// Default `--limit` for `cr find` and `cr grep` (default 20).
+    pub find_limit: Option<usize>,
+    /// Default `--window` for `cr handoff mark` (default 10).
+    pub window: Option<usize>,
+}
+
+/// Keys accepted by `cr config get/set`, in file order.
+pub const KEYS: &[&str] = &[
+    "require_store",
+    "max_len",
+    "detect_secrets",
+    "ls_limit",
+    "find_limit",
+    "window",
+];
+
+impl Config {
+    /// Keys set in `over` replace those in `self`.
+    fn merged(self, over: Config) -> Config {
+        Config {
+            require_store: over.require_store.or(self.require_store),
+            max_len: over.max_len.or(self.max_len),
+            detect_secrets: over.detect_secrets.or(self.detect_secrets),
+            ls_limit: over.ls_limit.or(self.ls_limit),
+            find_limit: over.find_limit.or(self.find_limit),
+            window: over.window.or(self.window),
+        }
+    }
+
+    /// `cr ls` row count: the flag if given, else `ls_limit`, else the built-in default.
+    pub fn ls_limit_or(&self, flag: Option<usize>) -> usize {
+        flag.or(self.ls_limit).unwrap_or(DEFAULT_LIMIT)
+    }
+
+    /// `cr find`/`cr grep` limit, with the same precedence as [`Config::ls_limit_or`].
+    pub fn find_limit_or(&self, flag: Option<usize>) -> usize {
+        flag.or(self.find_limit).unwrap_or(DEFAULT_LIMIT)
+    }
+
+    /// `cr handoff mark` window, with the same precedence as [`Config::ls_limit_or`].
+    pub fn window_or(&self, flag: Option<usize>) -> usize {
+        flag.or(self.window).unwrap_or(DEFAULT_WINDOW)
+    }
+
+    /// The value of `key` as written in the file, or `None` when unset.
+    pub fn get(&self, key: &str) -> Result<Option<String>> {
+        Ok(match key {
+            "require_store" => self.require_store.map(|v| v.to_string()),
+            "max_len" => self.max_len.map(|v| v.to_string()),
+            "detect_secrets" => self.detect_secrets.map(|v| v.to_string()),
+            "ls_limit" => self.ls_limit.map(|v| v.to_string()),
+            "find_limit" => self.find_limit.map(|v| v.to_string()),
+            "window" => self.window.map(|v| v.to_string()),
+            _ => anyhow::bail!("unknown config key '{key}' (known: {})", KEYS.join(", ")),
+        })
+    }
+
+    /// Parse `raw` for `key` and store it.
+    pub fn set(&mut self, key: &str, raw: &str) -> Result<()> {
+        let flag = || -> Result<bool> {
+            raw.parse()
+                .map_err(|_| anyhow::anyhow!("{key} must be true or false, got '{raw}'"))
+        };
+        let count = || -> Result<usize> {
+            match raw.parse::<usize>() {
+                Ok(n) if n > 0 => Ok(n),
+                _ => anyhow::bail!("{key} must be a positive integer, got '{raw}'"),
+            }
+        };
+        match key {
+            "require_store" => self.require_store = Some(flag()?),
+            "max_len" => self.max_len = Some(count()?),
+            "detect_secrets" => self.detect_secrets = Some(flag()?),
+            "ls_limit" => self.ls_limit = Some(count()?),
+            "find_limit" => self.find_limit = Some(count()?),
+            "window" => self.window = Some(count()?),
+            _ => anyhow::bail!("unknown config key '{key}' (known: {})", KEYS.join(", ")),
+        }
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<()> {
+        for (key, value) in [
+            ("max_len", self.max_len),
+            ("ls_limit", self.ls_limit),
+            ("find_limit", self.find_limit),
+            ("window", self.window),
+        ] {
+            if value == Some(0) {
+                anyhow::bail!("{key} must be a positive integer");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// User-level config (`$XDG_CONFIG_HOME/crumbs/config.toml` or `~/.config/crumbs/config.toml`)
+/// overlaid with the store's `.crumbs/config.toml`. Missing files are empty configs.
+pub fn load(store_dir: &Path) -> Result<Config> {
+    let user = match user_config_path() {
+        Some(path) => read_config(&path)?,
+        None => Config::default(),
+    };
+    let store = read_config(&store_dir.join(CONFIG_FILE))?;
+    Ok(user.merged(store))
+}
+
+/// Only the store's own `.crumbs/config.toml`, for `cr config set`.
+pub fn load_store(store_dir: &Path) -> Result<Config> {
+    read_config(&store_dir.join(CONFIG_FILE))
+}
+
+/// Replace `.crumbs/config.toml` via a temp file and rename.
+pub fn save_store(store_dir: &Path, config: &Config) -> Result<()> {
+    let path = store_dir.join(CONFIG_FILE);
+    let tmp_path = store_dir.join(format!("{CONFIG_FILE}.tmp"));
+    let src = toml::to_string(config).context("serialize config")?;
+    std::fs::write(&tmp_path, src).with_context(|| format!("write {}", tmp_path.display()))?;
+    std::fs::rename(&tmp_path, &path)
+        .with_context(|| format!("rename {} -> {}", tmp_path.display(), path.display()))
+}
+
+fn user_config_path() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")))?;
+    Some(base.join("crumbs").join(CONFIG_FILE))
+}
+
+fn read_config(path: &Path) -> Result<Config> {
+    let src = match std::fs::read_to_string(path) {
+        Ok(s) => s,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Config::default()),
+        Err(e) => return Err(e).with_context(|| format!("read {}", path.display())),
+    };
+    let config: Config =
+        toml::from_str(&src).with_context(|| format!("parse {}", path.display()))?;
+    config
+        .validate()
+        .with_context(|| format!("invalid {}", path.display()))?;
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    #[test]
+    fn flag_beats_config_beats_default() {
+        let unset = Config::default();
+        let set = Config {
+            ls_limit: Some(7),
+            find_limit: Some(8),
+            window: Some(9),
+            ..Default::default()
+        };
+        assert_eq!(unset.ls_limit_or(None), DEFAULT_LIMIT);
+        assert_eq!(set.ls_limit_or(None), 7);
+        assert_eq!(set.ls_limit_or(Some(3)), 3);
+        assert_eq!(unset.find_limit_or(None), DEFAULT_LIMIT);
+        assert_eq!(set.find_limit_or(None), 8);
+        assert_eq!(set.find_limit_or(Some(3)), 3);
+        assert_eq!(unset.window_or(None), DEFAULT_WINDOW);
+        assert_eq!(set.window_or(None), 9);
+        assert_eq!(set.window_or(Some(3)), 3);
+    }
+
+    #[test]
+    fn store_config_overrides_user_config() {
+        let user = Config {
+            ls_limit: Some(5),
+            window: Some(6),
+            ..Default::default()
+        };
+        let store = Config {
+            ls_limit: Some(50),
+            ..Default::default()
+        };
+        let merged = user.merged(store);
+        assert_eq!(merged.ls_limit, Some(50));
+        assert_eq!(merged.window, Some(6));
+    }
+
+    #[test]
+    fn set_then_save_round_trips_and_missing_file_is_empty() {
+        let dir = TempDir::new("config-round-trip");
+        assert!(load_store(dir.path()).unwrap().ls_limit.is_none());
+
+        let mut config = Config::default();
+        config.set("ls_limit", "30").unwrap();
+        config.set("detect_secrets", "true").unwrap();
+        save_store(dir.path(), &config).unwrap();
+        let loaded = load_store(dir.path()).unwrap();
+        assert_eq!(loaded.get("ls_limit").unwrap().as_deref(), Some("30"));
+        assert_eq!(
+            loaded.get("detect_secrets").unwrap().as_deref(),
+            Some("true")
+        );
+        assert_eq!(loaded.get("window").unwrap(), None);
+    }
+
+    #[test]
+    fn counts_must_be_positive() {
+        let mut config = Config::default();
+        assert!(config.set("window", "0").is_err());
+        assert!(config.set("window", "-2").is_err());
+        assert!(config.set("nope", "1").is_err());
+
+        let dir = TempDir::new("config-zero");
+        std::fs::write(dir.path().join(CONFIG_FILE), "find_limit = 0\n").unwrap();
+        assert!(load_store(dir.path()).is_err());
+    }
+}
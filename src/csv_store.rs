@@ -1,23 +1,134 @@
 use anyhow::{Context, Result};
 use csv::{ReaderBuilder, WriterBuilder};
+use regex::Regex;
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::fs::{self, OpenOptions};
-use std::path::Path;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use unicode_segmentation::UnicodeSegmentation;
 
-const MEMORIES_HEADER: &str = "id,kind,text,ts_utc,cwd,git_branch,git_head\n";
+const MEMORIES_HEADER: &str =
+    "id,kind,text,ts_utc,cwd,git_branch,git_head,git_diffstat,parent_id,seq,tags,note\n";
 const HANDOFFS_HEADER: &str =
-    "id,ts_utc,from_memory_id,to_memory_id,suggested_window,cwd,git_branch,git_head\n";
+    "id,ts_utc,from_memory_id,to_memory_id,suggested_window,cwd,git_branch,git_head,note\n";
+const LINKS_HEADER: &str = "from_id,to_id,ts_utc\n";
+const EVENTS_HEADER: &str = "ts_utc,event,handoff_id\n";
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// What a memory records: a `what` (fact/constraint/change) or a `why` (rationale).
+/// Stored and printed as the lowercase word; any other value in the CSV fails to parse.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize,
+    Deserialize,
+    clap::ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    What,
+    Why,
+}
+
+impl Kind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Kind::What => "what",
+            Kind::Why => "why",
+        }
+    }
+}
+
+impl std::fmt::Display for Kind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for Kind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "what" => Ok(Kind::What),
+            "why" => Ok(Kind::Why),
+            other => anyhow::bail!("unknown kind '{other}' (expected what or why)"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct MemoryRecord {
     pub id: String,
-    pub kind: String,
+    pub kind: Kind,
     pub text: String,
     pub ts_utc: String,
     pub cwd: String,
     pub git_branch: Option<String>,
     pub git_head: Option<String>,
+    /// Compact `git diff --shortstat` at record time, e.g. "3 files, +40 -12".
+    #[serde(default)]
+    pub git_diffstat: Option<String>,
+    /// Memory this one continues, forming an explicit thread (see `cr show --thread`).
+    #[serde(default)]
+    pub parent_id: Option<String>,
+    /// Append position (1-based) and the primary sort key, so ordering survives clock skew and
+    /// backdated timestamps. Rows written before this column existed get one by file order
+    /// when read.
+    #[serde(default)]
+    pub seq: u64,
+    /// Comma-separated topic tags (see `cr tag`); empty when untagged.
+    #[serde(default)]
+    pub tags: String,
+    /// Long-form note file, relative to `.crumbs/` (e.g. `notes/cr-ab12.md`; see `cr open`).
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+impl MemoryRecord {
+    pub fn tag_list(&self) -> impl Iterator<Item = &str> {
+        self.tags.split(',').filter(|t| !t.is_empty())
+    }
+
+    /// Add `tags` not already present, keeping existing order. Returns how many were added.
+    pub fn add_tags(&mut self, tags: &[String]) -> usize {
+        let mut list: Vec<String> = self.tag_list().map(str::to_string).collect();
+        let before = list.len();
+        for t in tags {
+            if !list.contains(t) {
+                list.push(t.clone());
+            }
+        }
+        let added = list.len() - before;
+        self.tags = list.join(",");
+        added
+    }
+
+    /// Remove any of `tags`. Returns how many were removed.
+    pub fn remove_tags(&mut self, tags: &[String]) -> usize {
+        let list: Vec<String> = self.tag_list().map(str::to_string).collect();
+        let kept: Vec<String> = list.iter().filter(|t| !tags.contains(t)).cloned().collect();
+        let removed = list.len() - kept.len();
+        self.tags = kept.join(",");
+        removed
+    }
+}
+
+/// Tags are stored comma-separated, so they may not contain commas or whitespace.
+pub fn validate_tag(tag: &str) -> Result<()> {
+    if tag.is_empty() {
+        anyhow::bail!("tag is empty");
+    }
+    if tag.contains(',') || tag.chars().any(char::is_whitespace) {
+        anyhow::bail!("tag '{tag}' may not contain commas or whitespace");
+    }
+    Ok(())
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -30,27 +141,66 @@ pub struct HandoffRecord {
     pub cwd: String,
     pub git_branch: Option<String>,
     pub git_head: Option<String>,
+    /// Short label for the checkpoint, shown by `handoff open`.
+    #[serde(default)]
+    pub note: Option<String>,
 }
 
-pub type MemoryRow = (
-    String,
-    String,
-    String,
-    String,
-    String,
-    Option<String>,
-    Option<String>,
-);
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LinkRecord {
+    pub from_id: String,
+    pub to_id: String,
+    pub ts_utc: String,
+}
+
+/// Something that happened to the store beyond a write, e.g. a handoff being resumed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventRecord {
+    pub ts_utc: String,
+    pub event: String,
+    pub handoff_id: Option<String>,
+}
 
 pub fn ensure_memories_file(memories_csv_path: &Path) -> Result<()> {
     ensure_csv_file(memories_csv_path, MEMORIES_HEADER)?;
     Ok(())
 }
 
+/// Whether the memories file was written with an older header (and so cannot be appended to).
+pub fn memories_header_outdated(memories_csv_path: &Path) -> Result<bool> {
+    header_outdated(memories_csv_path, MEMORIES_HEADER)
+}
+
+/// Rewrite an older-format memories file with the current header. Columns missing from the
+/// old header read as empty.
+pub fn migrate_memories(memories_csv_path: &Path) -> Result<()> {
+    let memories = read_memories(memories_csv_path)?;
+    rewrite_memories(memories_csv_path, &memories)
+}
+
+/// Whether the handoffs file was written with an older header.
+pub fn handoffs_header_outdated(handoffs_csv_path: &Path) -> Result<bool> {
+    header_outdated(handoffs_csv_path, HANDOFFS_HEADER)
+}
+
+/// Rewrite an older-format handoffs file with the current header.
+pub fn migrate_handoffs(handoffs_csv_path: &Path) -> Result<()> {
+    let handoffs = read_handoffs(handoffs_csv_path)?;
+    rewrite_handoffs(handoffs_csv_path, &handoffs)
+}
+
 pub fn ensure_handoffs_file(handoffs_csv_path: &Path) -> Result<()> {
     ensure_csv_file(handoffs_csv_path, HANDOFFS_HEADER)
 }
 
+pub fn ensure_links_file(links_csv_path: &Path) -> Result<()> {
+    ensure_csv_file(links_csv_path, LINKS_HEADER)
+}
+
+pub fn ensure_events_file(events_csv_path: &Path) -> Result<()> {
+    ensure_csv_file(events_csv_path, EVENTS_HEADER)
+}
+
 pub fn read_memories(memories_csv_path: &Path) -> Result<Vec<MemoryRecord>> {
     if !memories_csv_path.exists() {
         return Ok(Vec::new());
@@ -67,13 +217,80 @@ pub fn read_memories(memories_csv_path: &Path) -> Result<Vec<MemoryRecord>> {
             row.with_context(|| format!("parse {}", memories_csv_path.display()))?;
         out.push(record);
     }
+    backfill_seq(&mut out);
     Ok(out)
 }
 
+/// Give rows without a `seq` (read as 0) the next number after the row before them.
+fn backfill_seq(memories: &mut [MemoryRecord]) {
+    let mut last = 0;
+    for m in memories.iter_mut() {
+        if m.seq == 0 {
+            m.seq = last + 1;
+        }
+        last = last.max(m.seq);
+    }
+}
+
+/// `seq` for the next appended memory.
+pub fn next_seq(memories: &[MemoryRecord]) -> u64 {
+    memories.iter().map(|m| m.seq).max().unwrap_or(0) + 1
+}
+
 pub fn append_memory(memories_csv_path: &Path, rec: &MemoryRecord) -> Result<()> {
     append_csv_row(memories_csv_path, rec)
 }
 
+/// Serialize memories as CSV text (header included), in the on-disk format.
+pub fn memories_csv_string(memories: &[MemoryRecord]) -> Result<String> {
+    let mut writer = WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(Vec::new());
+    for rec in memories {
+        writer.serialize(rec).context("serialize csv")?;
+    }
+    let body = writer
+        .into_inner()
+        .map_err(|e| anyhow::anyhow!("flush csv: {}", e.error()))?;
+    let body = String::from_utf8(body).context("csv utf8")?;
+    Ok(format!("{MEMORIES_HEADER}{body}"))
+}
+
+/// Parse memories exported as a JSON array, NDJSON, or CSV with a header row. The format is
+/// picked from the first non-blank character (`[`, `{`, or anything else for CSV). Extra JSON
+/// keys such as `text_marked` are ignored.
+pub fn parse_memories(src: &str) -> Result<Vec<MemoryRecord>> {
+    let mut out: Vec<MemoryRecord> = match src.trim_start().chars().next() {
+        None => Vec::new(),
+        Some('[') => serde_json::from_str(src).context("parse json array")?,
+        Some('{') => src
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(idx, line)| {
+                serde_json::from_str(line).with_context(|| format!("parse json line {}", idx + 1))
+            })
+            .collect::<Result<_>>()?,
+        Some(_) => {
+            let mut reader = ReaderBuilder::new()
+                .has_headers(true)
+                .from_reader(src.as_bytes());
+            let mut out = Vec::new();
+            for row in reader.deserialize() {
+                out.push(row.context("parse csv")?);
+            }
+            out
+        }
+    };
+    backfill_seq(&mut out);
+    Ok(out)
+}
+
+/// Replace the whole memories file (header included) via temp file + rename.
+pub fn rewrite_memories(memories_csv_path: &Path, memories: &[MemoryRecord]) -> Result<()> {
+    rewrite_csv(memories_csv_path, MEMORIES_HEADER, memories)
+}
+
 pub fn read_handoffs(handoffs_csv_path: &Path) -> Result<Vec<HandoffRecord>> {
     if !handoffs_csv_path.exists() {
         return Ok(Vec::new());
@@ -97,16 +314,165 @@ pub fn append_handoff(handoffs_csv_path: &Path, rec: &HandoffRecord) -> Result<(
     append_csv_row(handoffs_csv_path, rec)
 }
 
+pub fn rewrite_handoffs(handoffs_csv_path: &Path, handoffs: &[HandoffRecord]) -> Result<()> {
+    rewrite_csv(handoffs_csv_path, HANDOFFS_HEADER, handoffs)
+}
+
+pub fn read_links(links_csv_path: &Path) -> Result<Vec<LinkRecord>> {
+    if !links_csv_path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut reader = ReaderBuilder::new()
+        .has_headers(true)
+        .from_path(links_csv_path)
+        .with_context(|| format!("open {}", links_csv_path.display()))?;
+
+    let mut out = Vec::new();
+    for row in reader.deserialize() {
+        let record: LinkRecord =
+            row.with_context(|| format!("parse {}", links_csv_path.display()))?;
+        out.push(record);
+    }
+    Ok(out)
+}
+
+pub fn append_link(links_csv_path: &Path, rec: &LinkRecord) -> Result<()> {
+    append_csv_row(links_csv_path, rec)
+}
+
+pub fn append_event(events_csv_path: &Path, rec: &EventRecord) -> Result<()> {
+    append_csv_row(events_csv_path, rec)
+}
+
+/// Newest-first order for memories: `seq` descending, then id descending for the rare
+/// duplicate `seq` (e.g. hand-merged files). `ts_utc` is for display and time filters only.
+pub fn newest_first(a: &MemoryRecord, b: &MemoryRecord) -> std::cmp::Ordering {
+    b.seq.cmp(&a.seq).then_with(|| b.id.cmp(&a.id))
+}
+
 pub fn latest_memory(memories: &[MemoryRecord]) -> Option<MemoryRecord> {
-    let mut rows = memories.to_vec();
-    rows.sort_by(|a, b| b.ts_utc.cmp(&a.ts_utc));
-    rows.into_iter().next()
+    memories.iter().min_by(|a, b| newest_first(a, b)).cloned()
 }
 
+/// The most recently marked handoff. handoffs.csv is append-only (squash keeps it oldest
+/// first), so a row's position is its `seq`: ordering never depends on clocks or on two
+/// checkpoints sharing a millisecond.
 pub fn latest_handoff(handoffs: &[HandoffRecord]) -> Option<HandoffRecord> {
+    handoffs.last().cloned()
+}
+
+/// `from` for a new handoff: where the previous one ended, or, for the first checkpoint, the
+/// memory just past the `window` newest so the slice holds about `window` memories (`None`,
+/// i.e. `<start>`, when there are no more than that).
+pub fn next_handoff_from(
+    memories: &[MemoryRecord],
+    prev: Option<&HandoffRecord>,
+    window: usize,
+) -> Option<String> {
+    if let Some(prev) = prev {
+        return Some(prev.to_memory_id.clone());
+    }
+    let mut sorted = memories.to_vec();
+    sorted.sort_by(newest_first);
+    sorted.get(window).map(|m| m.id.clone())
+}
+
+/// Collapse all but the newest `keep_last` handoffs into one checkpoint spanning from the
+/// oldest squashed `from` to the newest squashed `to`, with the widest window. The merged
+/// record keeps the newest squashed handoff's id and metadata. Returns the rewritten log
+/// (oldest first), the merged record, and how many handoffs it replaced, or `None` when fewer
+/// than two would be squashed.
+pub fn squash_handoffs(
+    handoffs: &[HandoffRecord],
+    keep_last: usize,
+) -> Option<(Vec<HandoffRecord>, HandoffRecord, usize)> {
     let mut rows = handoffs.to_vec();
-    rows.sort_by(|a, b| b.ts_utc.cmp(&a.ts_utc));
-    rows.into_iter().next()
+    let cut = rows.len().saturating_sub(keep_last);
+    if cut < 2 {
+        return None;
+    }
+    let kept = rows.split_off(cut);
+    let oldest = rows.first()?;
+    let mut merged = rows.last()?.clone();
+    merged.from_memory_id = oldest.from_memory_id.clone();
+    merged.suggested_window = rows.iter().map(|h| h.suggested_window).max()?;
+
+    let squashed = rows.len();
+    let mut out = vec![merged.clone()];
+    out.extend(kept);
+    Some((out, merged, squashed))
+}
+
+/// Memories covered by a handoff: newer than its `from` memory, up to and including its `to`
+/// memory, newest first. A missing `to` memory yields an empty slice; a missing `from`
+/// memory is treated as `<start>`. Bounds compare `seq`, like [`newest_first`].
+pub fn handoff_slice(memories: &[MemoryRecord], handoff: &HandoffRecord) -> Vec<MemoryRecord> {
+    handoff_slice_bounded(memories, handoff, false)
+}
+
+/// Ids of handoffs whose slice contains memory `id`, oldest handoff first. Handoffs whose
+/// `to` memory is gone have an empty slice and never match.
+pub fn handoffs_containing(
+    memories: &[MemoryRecord],
+    handoffs: &[HandoffRecord],
+    id: &str,
+) -> Vec<String> {
+    handoffs
+        .iter()
+        .filter(|h| handoff_slice(memories, h).iter().any(|m| m.id == id))
+        .map(|h| h.id.clone())
+        .collect()
+}
+
+/// Like [`handoff_slice`], optionally making the lower bound inclusive so the `from` memory
+/// itself is part of the slice.
+pub fn handoff_slice_bounded(
+    memories: &[MemoryRecord],
+    handoff: &HandoffRecord,
+    include_from: bool,
+) -> Vec<MemoryRecord> {
+    let Some(to) = memories.iter().find(|m| m.id == handoff.to_memory_id) else {
+        return Vec::new();
+    };
+    let from_seq = handoff
+        .from_memory_id
+        .as_ref()
+        .and_then(|from_id| memories.iter().find(|m| m.id == *from_id))
+        .map(|m| m.seq);
+
+    let mut slice: Vec<MemoryRecord> = memories
+        .iter()
+        .filter(|m| m.seq <= to.seq)
+        .filter(|m| match from_seq {
+            Some(seq) if include_from => m.seq >= seq,
+            Some(seq) => m.seq > seq,
+            None => true,
+        })
+        .cloned()
+        .collect();
+    slice.sort_by(newest_first);
+    slice
+}
+
+/// Memories outside every handoff slice (what a newly onboarded agent would never be shown),
+/// newest first.
+pub fn uncovered_memories(
+    memories: &[MemoryRecord],
+    handoffs: &[HandoffRecord],
+) -> Vec<MemoryRecord> {
+    let covered: HashSet<String> = handoffs
+        .iter()
+        .flat_map(|h| handoff_slice(memories, h))
+        .map(|m| m.id)
+        .collect();
+    let mut out: Vec<MemoryRecord> = memories
+        .iter()
+        .filter(|m| !covered.contains(&m.id))
+        .cloned()
+        .collect();
+    out.sort_by(newest_first);
+    out
 }
 
 pub fn resolve_handoff(handoffs: &[HandoffRecord], id_prefix: &str) -> Result<HandoffRecord> {
@@ -127,8 +493,13 @@ pub fn resolve_handoff(handoffs: &[HandoffRecord], id_prefix: &str) -> Result<Ha
     Ok(matches[0].clone())
 }
 
+/// Resolve an id or unique prefix. A complete id wins outright, so a caller-chosen id such as
+/// `ab12` stays reachable after `cr-ab12x` appears.
 pub fn resolve_memory_id(memories: &[MemoryRecord], id_prefix: &str) -> Result<String> {
     let candidates = build_prefix_candidates(id_prefix, "cr-", "c_");
+    if let Some(id) = exact_memory_id(memories, &candidates) {
+        return Ok(id);
+    }
     let mut seen: HashSet<String> = HashSet::new();
     let mut matches: Vec<&MemoryRecord> = memories
         .iter()
@@ -143,34 +514,1044 @@ pub fn resolve_memory_id(memories: &[MemoryRecord], id_prefix: &str) -> Result<S
         anyhow::bail!("id prefix '{id_prefix}' is ambiguous");
     }
 
-    matches.sort_by(|a, b| b.ts_utc.cmp(&a.ts_utc));
+    matches.sort_by(|a, b| newest_first(a, b));
     Ok(matches[0].id.clone())
 }
 
-pub fn list_memories(memories: &[MemoryRecord], limit: usize) -> Vec<MemoryRow> {
+/// What would dangle if memory `id` were deleted: handoffs with it as a boundary, and memories
+/// continuing it (`parent_id`).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Dependents {
+    pub handoffs: Vec<String>,
+    pub children: Vec<String>,
+}
+
+impl Dependents {
+    pub fn is_empty(&self) -> bool {
+        self.handoffs.is_empty() && self.children.is_empty()
+    }
+
+    /// Refuse to delete `id` while anything depends on it, unless `force`.
+    pub fn ensure_removable(&self, id: &str, force: bool) -> Result<()> {
+        if force {
+            return Ok(());
+        }
+        if !self.handoffs.is_empty() {
+            anyhow::bail!(
+                "{id} is a boundary of handoff(s) {}; pass --force to delete it anyway",
+                self.handoffs.join(", ")
+            );
+        }
+        if !self.children.is_empty() {
+            anyhow::bail!(
+                "{id} is the parent of {}; pass --force to delete it anyway",
+                self.children.join(", ")
+            );
+        }
+        Ok(())
+    }
+}
+
+pub fn dependents(memories: &[MemoryRecord], handoffs: &[HandoffRecord], id: &str) -> Dependents {
+    Dependents {
+        handoffs: handoffs
+            .iter()
+            .filter(|h| h.to_memory_id == id || h.from_memory_id.as_deref() == Some(id))
+            .map(|h| h.id.clone())
+            .collect(),
+        children: memories
+            .iter()
+            .filter(|m| m.parent_id.as_deref() == Some(id))
+            .map(|m| m.id.clone())
+            .collect(),
+    }
+}
+
+/// One entry of the combined memory/handoff timeline.
+#[derive(Debug, Clone)]
+pub enum FeedEntry {
+    Memory(MemoryRecord),
+    Handoff(HandoffRecord),
+}
+
+impl FeedEntry {
+    pub fn ts_utc(&self) -> &str {
+        match self {
+            FeedEntry::Memory(m) => &m.ts_utc,
+            FeedEntry::Handoff(h) => &h.ts_utc,
+        }
+    }
+}
+
+/// Memories and handoffs interleaved, newest first.
+pub fn recent_feed(
+    memories: &[MemoryRecord],
+    handoffs: &[HandoffRecord],
+    limit: usize,
+) -> Vec<FeedEntry> {
+    let mut feed: Vec<FeedEntry> = memories
+        .iter()
+        .cloned()
+        .map(FeedEntry::Memory)
+        .chain(handoffs.iter().cloned().map(FeedEntry::Handoff))
+        .collect();
+    feed.sort_by(|a, b| b.ts_utc().cmp(a.ts_utc()));
+    feed.truncate(limit);
+    feed
+}
+
+/// One repair made by `cr relink` for a reference to a memory that no longer exists.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RelinkChange {
+    DropLink {
+        from_id: String,
+        to_id: String,
+    },
+    RepointTo {
+        handoff: String,
+        old: String,
+        new: String,
+    },
+    RepointFrom {
+        handoff: String,
+        old: String,
+        new: Option<String>,
+    },
+    DropHandoff {
+        handoff: String,
+        to_memory_id: String,
+    },
+    ClearParent {
+        memory: String,
+        parent: String,
+    },
+}
+
+impl RelinkChange {
+    pub fn describe(&self) -> String {
+        match self {
+            RelinkChange::DropLink { from_id, to_id } => {
+                format!("link {from_id} -> {to_id}: dropped")
+            }
+            RelinkChange::RepointTo { handoff, old, new } => {
+                format!("handoff {handoff}: to {old} -> {new}")
+            }
+            RelinkChange::RepointFrom { handoff, old, new } => format!(
+                "handoff {handoff}: from {old} -> {}",
+                new.as_deref().unwrap_or("<start>")
+            ),
+            RelinkChange::DropHandoff {
+                handoff,
+                to_memory_id,
+            } => format!("handoff {handoff}: dropped (to {to_memory_id} and no earlier memory)"),
+            RelinkChange::ClearParent { memory, parent } => {
+                format!("memory {memory}: parent {parent} cleared")
+            }
+        }
+    }
+}
+
+/// Memories, handoffs, and links with every dangling memory reference repaired, plus the
+/// changes made.
+#[derive(Debug, Clone)]
+pub struct RelinkPlan {
+    pub changes: Vec<RelinkChange>,
+    pub memories: Vec<MemoryRecord>,
+    pub handoffs: Vec<HandoffRecord>,
+    pub links: Vec<LinkRecord>,
+}
+
+/// Repair references to memories that are gone (after `purge` or manual edits).
+///
+/// Links with a missing end are dropped, and so are `parent_id`s naming a missing memory (the
+/// child becomes a thread start). A handoff whose `to_memory_id` is gone is re-pointed
+/// to the newest surviving memory (by `seq`) recorded at or before the handoff itself, or
+/// dropped if there is none; the handoff's timestamp is only used as that cut-off, since
+/// handoffs carry no `seq`. A missing `from_memory_id` is re-pointed the same way, using the time of the
+/// handoff that ended at it (or cleared to `<start>` when no such handoff exists).
+pub fn relink_plan(
+    memories: &[MemoryRecord],
+    handoffs: &[HandoffRecord],
+    links: &[LinkRecord],
+) -> RelinkPlan {
+    let alive: HashSet<&str> = memories.iter().map(|m| m.id.as_str()).collect();
+    let latest_at_or_before = |ts: &str| -> Option<String> {
+        memories
+            .iter()
+            .filter(|m| m.ts_utc.as_str() <= ts)
+            .min_by(|a, b| newest_first(a, b))
+            .map(|m| m.id.clone())
+    };
+
+    let mut changes = Vec::new();
+    let mut kept_links = Vec::new();
+    for l in links {
+        if alive.contains(l.from_id.as_str()) && alive.contains(l.to_id.as_str()) {
+            kept_links.push(l.clone());
+        } else {
+            changes.push(RelinkChange::DropLink {
+                from_id: l.from_id.clone(),
+                to_id: l.to_id.clone(),
+            });
+        }
+    }
+
+    let mut kept_memories = memories.to_vec();
+    for m in kept_memories.iter_mut() {
+        if let Some(parent) = m.parent_id.clone()
+            && !alive.contains(parent.as_str())
+        {
+            changes.push(RelinkChange::ClearParent {
+                memory: m.id.clone(),
+                parent,
+            });
+            m.parent_id = None;
+        }
+    }
+
+    let mut kept_handoffs = Vec::new();
+    for h in handoffs {
+        let mut h = h.clone();
+        if !alive.contains(h.to_memory_id.as_str()) {
+            match latest_at_or_before(&h.ts_utc) {
+                Some(new) => {
+                    changes.push(RelinkChange::RepointTo {
+                        handoff: h.id.clone(),
+                        old: h.to_memory_id.clone(),
+                        new: new.clone(),
+                    });
+                    h.to_memory_id = new;
+                }
+                None => {
+                    changes.push(RelinkChange::DropHandoff {
+                        handoff: h.id.clone(),
+                        to_memory_id: h.to_memory_id.clone(),
+                    });
+                    continue;
+                }
+            }
+        }
+        if let Some(old) = h.from_memory_id.clone()
+            && !alive.contains(old.as_str())
+        {
+            let new = handoffs
+                .iter()
+                .rfind(|p| p.to_memory_id == old)
+                .and_then(|p| latest_at_or_before(&p.ts_utc));
+            changes.push(RelinkChange::RepointFrom {
+                handoff: h.id.clone(),
+                old,
+                new: new.clone(),
+            });
+            h.from_memory_id = new;
+        }
+        kept_handoffs.push(h);
+    }
+
+    RelinkPlan {
+        changes,
+        memories: kept_memories,
+        handoffs: kept_handoffs,
+        links: kept_links,
+    }
+}
+
+/// Length of memory text as a user sees it: extended grapheme clusters, so an emoji or an
+/// accented letter written with combining marks counts once.
+pub fn text_len(text: &str) -> usize {
+    text.graphemes(true).count()
+}
+
+/// First `max` grapheme clusters of `text`, with an ellipsis when cut.
+pub fn truncate_text(text: &str, max: usize) -> String {
+    let mut graphemes = text.graphemes(true);
+    let head: String = graphemes.by_ref().take(max).collect();
+    if graphemes.next().is_some() {
+        format!("{head}…")
+    } else {
+        head
+    }
+}
+
+/// Store-wide totals for `cr stats`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub memories: usize,
+    /// Memory count per kind, sorted by kind.
+    pub by_kind: Vec<(Kind, usize)>,
+    pub handoffs: usize,
+    pub oldest_ts: Option<String>,
+    pub newest_ts: Option<String>,
+    pub distinct_cwds: usize,
+    /// Distinct non-empty `git_branch` values.
+    pub distinct_branches: usize,
+}
+
+pub fn compute_stats(memories: &[MemoryRecord], handoffs: &[HandoffRecord]) -> Stats {
+    let mut by_kind: Vec<(Kind, usize)> = Vec::new();
+    for m in memories {
+        match by_kind.iter_mut().find(|(k, _)| *k == m.kind) {
+            Some((_, n)) => *n += 1,
+            None => by_kind.push((m.kind, 1)),
+        }
+    }
+    by_kind.sort();
+
+    let cwds: HashSet<&str> = memories.iter().map(|m| m.cwd.as_str()).collect();
+    let branches: HashSet<&str> = memories
+        .iter()
+        .filter_map(|m| m.git_branch.as_deref())
+        .collect();
+    Stats {
+        memories: memories.len(),
+        by_kind,
+        handoffs: handoffs.len(),
+        oldest_ts: memories.iter().map(|m| m.ts_utc.clone()).min(),
+        newest_ts: memories.iter().map(|m| m.ts_utc.clone()).max(),
+        distinct_cwds: cwds.len(),
+        distinct_branches: branches.len(),
+    }
+}
+
+/// Memory count and latest activity for one `git_branch` (`None` = recorded outside git or
+/// on a detached HEAD).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BranchStats {
+    pub branch: Option<String>,
+    pub count: usize,
+    pub latest_ts: String,
+}
+
+/// Per-branch memory counts, most recently active branch first.
+pub fn branch_stats(memories: &[MemoryRecord]) -> Vec<BranchStats> {
+    let mut out: Vec<BranchStats> = Vec::new();
+    for m in memories {
+        match out.iter_mut().find(|b| b.branch == m.git_branch) {
+            Some(b) => {
+                b.count += 1;
+                if m.ts_utc > b.latest_ts {
+                    b.latest_ts = m.ts_utc.clone();
+                }
+            }
+            None => out.push(BranchStats {
+                branch: m.git_branch.clone(),
+                count: 1,
+                latest_ts: m.ts_utc.clone(),
+            }),
+        }
+    }
+    out.sort_by(|a, b| {
+        b.latest_ts
+            .cmp(&a.latest_ts)
+            .then_with(|| a.branch.cmp(&b.branch))
+    });
+    out
+}
+
+/// The other end of a link, which may point at a memory that no longer exists.
+#[derive(Debug, Clone)]
+pub enum LinkedMemory {
+    Found(Box<MemoryRecord>),
+    Missing(String),
+}
+
+impl LinkedMemory {
+    pub fn id(&self) -> &str {
+        match self {
+            LinkedMemory::Found(m) => &m.id,
+            LinkedMemory::Missing(id) => id,
+        }
+    }
+}
+
+/// Whether a link between `a` and `b` exists, in either direction.
+pub fn is_linked(links: &[LinkRecord], a: &str, b: &str) -> bool {
+    links
+        .iter()
+        .any(|l| (l.from_id == a && l.to_id == b) || (l.from_id == b && l.to_id == a))
+}
+
+/// Memories linked to `id` in either direction, newest first.
+/// Dangling targets are listed after the resolved ones.
+pub fn linked_memories(
+    memories: &[MemoryRecord],
+    links: &[LinkRecord],
+    id: &str,
+) -> Vec<LinkedMemory> {
+    let mut seen: HashSet<&str> = HashSet::new();
+    let mut found: Vec<MemoryRecord> = Vec::new();
+    let mut missing: Vec<String> = Vec::new();
+    for link in links {
+        let other = if link.from_id == id {
+            &link.to_id
+        } else if link.to_id == id {
+            &link.from_id
+        } else {
+            continue;
+        };
+        if other == id || !seen.insert(other) {
+            continue;
+        }
+        match memories.iter().find(|m| m.id == *other) {
+            Some(m) => found.push(m.clone()),
+            None => missing.push(other.clone()),
+        }
+    }
+
+    found.sort_by(newest_first);
+    found
+        .into_iter()
+        .map(|m| LinkedMemory::Found(Box::new(m)))
+        .chain(missing.into_iter().map(LinkedMemory::Missing))
+        .collect()
+}
+
+/// A memory's thread: its ancestors (root first) and its descendants in depth-first order,
+/// children oldest first. Depths are relative to the memory itself.
+#[derive(Debug, Clone)]
+pub struct Thread {
+    pub ancestors: Vec<MemoryRecord>,
+    pub descendants: Vec<(usize, MemoryRecord)>,
+}
+
+/// Follow `parent_id` from `id` up to its root and down through every descendant.
+/// Parents that no longer exist end the chain; cycles are cut at the first repeat.
+pub fn thread_of(memories: &[MemoryRecord], id: &str) -> Thread {
+    let by_id = |id: &str| memories.iter().find(|m| m.id == id);
+
+    let mut seen: HashSet<&str> = HashSet::from([id]);
+    let mut ancestors = Vec::new();
+    let mut cur = by_id(id).and_then(|m| m.parent_id.as_deref());
+    while let Some(pid) = cur {
+        if !seen.insert(pid) {
+            break;
+        }
+        let Some(parent) = by_id(pid) else { break };
+        ancestors.push(parent.clone());
+        cur = parent.parent_id.as_deref();
+    }
+    ancestors.reverse();
+
+    let mut descendants = Vec::new();
+    let mut stack: Vec<(usize, &str)> = vec![(0, id)];
+    while let Some((depth, pid)) = stack.pop() {
+        let mut children: Vec<&MemoryRecord> = memories
+            .iter()
+            .filter(|m| m.parent_id.as_deref() == Some(pid))
+            .collect();
+        children.sort_by(|a, b| newest_first(b, a));
+        // Push newest first so the oldest child is visited first.
+        for child in children.into_iter().rev() {
+            if seen.insert(&child.id) {
+                stack.push((depth + 1, &child.id));
+            }
+        }
+        if depth > 0 {
+            let rec = by_id(pid).expect("descendant ids come from memories");
+            descendants.push((depth, rec.clone()));
+        }
+    }
+
+    Thread {
+        ancestors,
+        descendants,
+    }
+}
+
+/// The up-to-`n` memories recorded just before and just after `id` in timeline (oldest-first)
+/// order, plus the memory itself; either side is shorter at the ends of the log. `None` when
+/// `id` is not in `memories`.
+pub fn neighbors<'a>(
+    memories: &'a [MemoryRecord],
+    id: &str,
+    n: usize,
+) -> Option<(
+    Vec<&'a MemoryRecord>,
+    &'a MemoryRecord,
+    Vec<&'a MemoryRecord>,
+)> {
+    let mut timeline: Vec<&MemoryRecord> = memories.iter().collect();
+    timeline.sort_by(|a, b| newest_first(b, a));
+
+    let i = timeline.iter().position(|m| m.id == id)?;
+    let before = timeline[i.saturating_sub(n)..i].to_vec();
+    let after = timeline.iter().skip(i + 1).take(n).copied().collect();
+    Some((before, timeline[i], after))
+}
+
+/// Hits plus up to `n` memories on either side of each, in timeline (oldest-first) order.
+/// Overlapping or adjacent windows merge into one block, like `grep -C`; each entry is
+/// flagged with whether it is itself a hit.
+pub fn context_blocks<'a>(
+    memories: &'a [MemoryRecord],
+    hits: &HashSet<&str>,
+    n: usize,
+) -> Vec<Vec<(bool, &'a MemoryRecord)>> {
+    let mut timeline: Vec<&MemoryRecord> = memories.iter().collect();
+    timeline.sort_by(|a, b| newest_first(b, a));
+
+    let mut blocks: Vec<Vec<(bool, &MemoryRecord)>> = Vec::new();
+    let mut last_end: Option<usize> = None;
+    for (i, m) in timeline.iter().enumerate() {
+        if !hits.contains(m.id.as_str()) {
+            continue;
+        }
+        let start = i.saturating_sub(n);
+        let end = (i + n).min(timeline.len() - 1);
+        let from = match last_end {
+            Some(prev) if start <= prev + 1 => prev + 1,
+            _ => {
+                blocks.push(Vec::new());
+                start
+            }
+        };
+        let block = blocks.last_mut().expect("block pushed above");
+        for rec in timeline.iter().take(end + 1).skip(from) {
+            block.push((hits.contains(rec.id.as_str()), *rec));
+        }
+        last_end = Some(last_end.map_or(end, |prev| prev.max(end)));
+    }
+    blocks
+}
+
+/// Thread starts — memories without a parent (or whose parent no longer exists) — mapped to
+/// how many memories descend from them.
+pub fn thread_roots(memories: &[MemoryRecord]) -> HashMap<String, usize> {
+    let ids: HashSet<&str> = memories.iter().map(|m| m.id.as_str()).collect();
+    let mut children: HashMap<&str, Vec<&str>> = HashMap::new();
+    let mut roots = Vec::new();
+    for m in memories {
+        match m.parent_id.as_deref() {
+            Some(pid) if ids.contains(pid) => children.entry(pid).or_default().push(&m.id),
+            _ => roots.push(m.id.as_str()),
+        }
+    }
+
+    let mut out = HashMap::new();
+    for root in roots {
+        let mut seen: HashSet<&str> = HashSet::from([root]);
+        let mut stack = vec![root];
+        while let Some(id) = stack.pop() {
+            for &child in children.get(id).into_iter().flatten() {
+                if seen.insert(child) {
+                    stack.push(child);
+                }
+            }
+        }
+        out.insert(root.to_string(), seen.len() - 1);
+    }
+    out
+}
+
+/// Existing memory of the same kind whose text matches after whitespace/case normalization.
+pub fn find_duplicate<'a>(
+    memories: &'a [MemoryRecord],
+    kind: Kind,
+    text: &str,
+) -> Option<&'a MemoryRecord> {
+    let needle = normalize_text(text);
+    memories
+        .iter()
+        .find(|m| m.kind == kind && normalize_text(&m.text) == needle)
+}
+
+fn normalize_text(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Record predicates shared by listing and search commands.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryFilter {
+    /// Keep records strictly newer than this canonical `ts_utc` value.
+    pub after_ts: Option<String>,
+    /// Keep only records of this kind.
+    pub kind: Option<Kind>,
+    /// Keep only records whose id is in this set.
+    pub ids: Option<HashSet<String>>,
+    /// Keep records with `lo <= ts_utc <= hi` (canonical `ts_utc` values).
+    pub window: Option<(String, String)>,
+    /// Keep records with `ts_utc >= since` (canonical `ts_utc` value).
+    pub since: Option<String>,
+    /// Keep records with `ts_utc <= until` (canonical `ts_utc` value).
+    pub until: Option<String>,
+    /// Keep only records captured outside git (no branch and no head).
+    pub only_no_git: bool,
+    /// Keep records whose text is at least this many graphemes long (see [`text_len`]).
+    pub min_len: Option<usize>,
+    /// Keep records whose text is at most this many graphemes long.
+    pub max_len: Option<usize>,
+    /// Keep only records carrying this tag.
+    pub tag: Option<String>,
+    /// Keep records whose stored cwd is this root-relative path (as recorded by `cr what`).
+    pub cwd: Option<String>,
+    /// With `cwd`: also keep records from directories beneath it.
+    pub cwd_recursive: bool,
+    /// Keep only records captured on this git branch (records outside git never match).
+    pub branch: Option<String>,
+}
+
+impl MemoryFilter {
+    pub fn matches(&self, m: &MemoryRecord) -> bool {
+        if let Some((lo, hi)) = self.window.as_ref()
+            && (m.ts_utc < *lo || m.ts_utc > *hi)
+        {
+            return false;
+        }
+        if let Some(ids) = self.ids.as_ref()
+            && !ids.contains(&m.id)
+        {
+            return false;
+        }
+        if let Some(kind) = self.kind
+            && m.kind != kind
+        {
+            return false;
+        }
+        if let Some(ts) = self.after_ts.as_deref()
+            && m.ts_utc.as_str() <= ts
+        {
+            return false;
+        }
+        if let Some(ts) = self.since.as_deref()
+            && m.ts_utc.as_str() < ts
+        {
+            return false;
+        }
+        if let Some(ts) = self.until.as_deref()
+            && m.ts_utc.as_str() > ts
+        {
+            return false;
+        }
+        if self.only_no_git && !recorded_outside_git(m) {
+            return false;
+        }
+        if let Some(tag) = self.tag.as_deref()
+            && !m.tag_list().any(|t| t == tag)
+        {
+            return false;
+        }
+        if let Some(cwd) = self.cwd.as_deref()
+            && !cwd_within(&m.cwd, cwd, self.cwd_recursive)
+        {
+            return false;
+        }
+        if let Some(branch) = self.branch.as_deref()
+            && m.git_branch.as_deref() != Some(branch)
+        {
+            return false;
+        }
+        if self.min_len.is_some() || self.max_len.is_some() {
+            let n = text_len(&m.text);
+            if self.min_len.is_some_and(|min| n < min) || self.max_len.is_some_and(|max| n > max) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Whether stored cwd `stored` is `want`, or (with `recursive`) somewhere beneath it. The
+/// root (`.`) contains every relative cwd; absolute cwds (outside the root) only match by
+/// their own prefix.
+fn cwd_within(stored: &str, want: &str, recursive: bool) -> bool {
+    if stored == want {
+        return true;
+    }
+    if !recursive {
+        return false;
+    }
+    if want == "." {
+        return !Path::new(stored).is_absolute();
+    }
+    stored
+        .strip_prefix(want)
+        .is_some_and(|rest| rest.starts_with('/'))
+}
+
+/// Whether a memory carries no git context at all (recorded outside a repo, or before its
+/// first commit).
+pub fn recorded_outside_git(m: &MemoryRecord) -> bool {
+    m.git_branch.is_none() && m.git_head.is_none()
+}
+
+pub fn filter_memories(memories: &[MemoryRecord], filter: &MemoryFilter) -> Vec<MemoryRecord> {
+    memories
+        .iter()
+        .filter(|m| filter.matches(m))
+        .cloned()
+        .collect()
+}
+
+/// Check a caller-chosen id: lowercase ASCII alphanumerics and `-`, and not a prefix of (or
+/// prefixed by) an existing id, so later prefix resolution stays unambiguous.
+pub fn validate_new_memory_id(memories: &[MemoryRecord], id: &str) -> Result<()> {
+    if id.is_empty()
+        || !id
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+    {
+        anyhow::bail!("invalid id '{id}': use lowercase letters, digits, and '-'");
+    }
+    if let Some(clash) = memories.iter().find(|m| {
+        let existing = m.id.to_ascii_lowercase();
+        existing.starts_with(id) || id.starts_with(&existing)
+    }) {
+        anyhow::bail!(
+            "id '{id}' would be ambiguous with existing id '{}'",
+            clash.id
+        );
+    }
+    Ok(())
+}
+
+pub fn list_memories(memories: &[MemoryRecord], limit: usize) -> Vec<MemoryRecord> {
+    let mut rows = memories.to_vec();
+    rows.sort_by(newest_first);
+    rows.into_iter().take(limit).collect()
+}
+
+/// A page of records strictly older than `cursor`, in [`newest_first`] order.
+/// Returns the page and the id to pass as the next cursor, or `None` when exhausted.
+pub fn page_after(
+    memories: &[MemoryRecord],
+    cursor: Option<&MemoryRecord>,
+    limit: usize,
+) -> (Vec<MemoryRecord>, Option<String>) {
+    let mut rows: Vec<MemoryRecord> = memories
+        .iter()
+        .filter(|m| match cursor {
+            Some(c) => newest_first(c, m).is_lt(),
+            None => true,
+        })
+        .cloned()
+        .collect();
+    rows.sort_by(newest_first);
+
+    let more = rows.len() > limit;
+    rows.truncate(limit);
+    let next = if more {
+        rows.last().map(|m| m.id.clone())
+    } else {
+        None
+    };
+    (rows, next)
+}
+
+/// Keep the leading records whose combined text length (see [`text_len`]) stays within
+/// `budget` chars. Returns the kept records and the number of chars they use.
+pub fn take_within_budget(records: Vec<MemoryRecord>, budget: usize) -> (Vec<MemoryRecord>, usize) {
+    let mut used = 0;
+    let mut out = Vec::new();
+    for rec in records {
+        let len = text_len(&rec.text);
+        if used + len > budget {
+            break;
+        }
+        used += len;
+        out.push(rec);
+    }
+    (out, used)
+}
+
+/// Field to order `ls` output by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    Ts,
+    Kind,
+    Cwd,
+    Text,
+    Seq,
+}
+
+/// Order records by `key`, breaking ties by id, both in the same direction.
+pub fn sort_memories(memories: &[MemoryRecord], key: SortKey, desc: bool) -> Vec<MemoryRecord> {
+    let mut rows = memories.to_vec();
+    rows.sort_by(|a, b| {
+        let ord = match key {
+            SortKey::Ts => a.ts_utc.cmp(&b.ts_utc),
+            SortKey::Kind => a.kind.cmp(&b.kind),
+            SortKey::Cwd => a.cwd.cmp(&b.cwd),
+            SortKey::Text => a.text.cmp(&b.text),
+            SortKey::Seq => a.seq.cmp(&b.seq),
+        }
+        .then_with(|| a.id.cmp(&b.id));
+        if desc { ord.reverse() } else { ord }
+    });
+    rows
+}
+
+pub fn first_memories(memories: &[MemoryRecord], limit: usize) -> Vec<MemoryRecord> {
     let mut rows = memories.to_vec();
-    rows.sort_by(|a, b| b.ts_utc.cmp(&a.ts_utc));
-    rows.into_iter().take(limit).map(to_memory_row).collect()
+    rows.sort_by(|a, b| newest_first(b, a));
+    rows.into_iter().take(limit).collect()
+}
+
+/// How an id prefix is resolved, for `cr show --explain-resolution`.
+#[derive(Debug, Clone)]
+pub struct ResolutionTrace {
+    /// Each prefix tried (as typed, then with `cr-`/legacy `c_` prepended) and how many
+    /// records it matched.
+    pub candidates: Vec<(String, usize)>,
+    /// Distinct matching ids, newest first. Resolution succeeds when there is one, or when
+    /// `exact` is set.
+    pub matched: Vec<String>,
+    /// A candidate that is a memory's complete id; it wins over other prefix matches.
+    pub exact: Option<String>,
 }
 
-pub fn show_memory(memories: &[MemoryRecord], id_prefix: &str) -> Result<MemoryRow> {
+pub fn trace_memory_resolution(memories: &[MemoryRecord], id_prefix: &str) -> ResolutionTrace {
+    let prefixes = build_prefix_candidates(id_prefix, "cr-", "c_");
+    let candidates = prefixes
+        .iter()
+        .map(|p| {
+            let n = memories
+                .iter()
+                .filter(|m| matches_any_prefix(&m.id, std::slice::from_ref(p)))
+                .count();
+            (p.clone(), n)
+        })
+        .collect();
+
+    let mut hits: Vec<&MemoryRecord> = memories
+        .iter()
+        .filter(|m| matches_any_prefix(&m.id, &prefixes))
+        .collect();
+    hits.sort_by(|a, b| newest_first(a, b));
+    let mut seen: HashSet<&str> = HashSet::new();
+    let matched = hits
+        .into_iter()
+        .filter(|m| seen.insert(&m.id))
+        .map(|m| m.id.clone())
+        .collect();
+    ResolutionTrace {
+        candidates,
+        matched,
+        exact: exact_memory_id(memories, &prefixes),
+    }
+}
+
+pub fn show_memory(memories: &[MemoryRecord], id_prefix: &str) -> Result<MemoryRecord> {
     let id = resolve_memory_id(memories, id_prefix)?;
     let rec = memories
         .iter()
         .find(|m| m.id == id)
         .with_context(|| format!("resolve id '{}'", id_prefix))?;
-    Ok(to_memory_row(rec.clone()))
+    Ok(rec.clone())
+}
+
+/// How a `find` query string is matched against memory text.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MatchMode {
+    /// The whole query as one substring.
+    #[default]
+    Phrase,
+    /// Every whitespace-separated term must appear.
+    All,
+    /// At least one term must appear.
+    Any,
 }
 
-pub fn find_memories(memories: &[MemoryRecord], query: &str, limit: usize) -> Vec<MemoryRow> {
+/// Result ordering for `find`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FindSort {
+    #[default]
+    Newest,
+    /// Most distinct query terms matched first, then newest.
+    Coverage,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FindOptions {
+    pub mode: MatchMode,
+    pub sort: FindSort,
+    /// Order by time oldest first (the coverage ranking, if any, still comes first).
+    pub oldest: bool,
+}
+
+pub fn find_memories(
+    memories: &[MemoryRecord],
+    query: &str,
+    opts: FindOptions,
+    limit: usize,
+) -> Vec<MemoryRecord> {
     let needle = query.to_lowercase();
+    let terms = query_terms(query);
+    let mut rows: Vec<(usize, MemoryRecord)> = memories
+        .iter()
+        .filter_map(|m| {
+            let text = m.text.to_lowercase();
+            let covered = coverage(&text, &terms);
+            let hit = match opts.mode {
+                MatchMode::Phrase => text.contains(&needle),
+                MatchMode::All => covered == terms.len(),
+                MatchMode::Any => covered > 0,
+            };
+            hit.then(|| (covered, m.clone()))
+        })
+        .collect();
+    let by_time = |a: &MemoryRecord, b: &MemoryRecord| {
+        if opts.oldest {
+            newest_first(b, a)
+        } else {
+            newest_first(a, b)
+        }
+    };
+    match opts.sort {
+        FindSort::Newest => rows.sort_by(|a, b| by_time(&a.1, &b.1)),
+        FindSort::Coverage => rows.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| by_time(&a.1, &b.1))),
+    }
+    rows.into_iter().take(limit).map(|(_, m)| m).collect()
+}
+
+/// Memories whose text contains the query's non-space characters in order (case-insensitive),
+/// best [`fuzzy_score`] first and newest first among ties, each paired with its score.
+pub fn fuzzy_find(
+    memories: &[MemoryRecord],
+    query: &str,
+    limit: usize,
+) -> Vec<(i64, MemoryRecord)> {
+    let mut rows: Vec<(i64, MemoryRecord)> = memories
+        .iter()
+        .filter_map(|m| fuzzy_score(&m.text, query).map(|s| (s, m.clone())))
+        .collect();
+    rows.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| newest_first(&a.1, &b.1)));
+    rows.truncate(limit);
+    rows
+}
+
+/// Score of the best in-order alignment of `query`'s characters (whitespace ignored) within
+/// `text`, or `None` when they don't all appear in order. Each matched char earns points, with
+/// bonuses for runs of consecutive matches and for matches at word starts; skipped chars between
+/// matches cost a little, so tight, word-aligned matches rank first.
+pub fn fuzzy_score(text: &str, query: &str) -> Option<i64> {
+    const MATCH: i64 = 16;
+    const CONSECUTIVE: i64 = 8;
+    const WORD_START: i64 = 8;
+    const GAP_OPEN: i64 = 3;
+    const GAP_EXTEND: i64 = 1;
+
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let query: Vec<char> = query
+        .to_lowercase()
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect();
+    if query.is_empty() {
+        return None;
+    }
+    let word_start = |j: usize| j == 0 || !text[j - 1].is_alphanumeric();
+
+    // best[j]: best score with the current query char matched at text[j].
+    let mut best: Vec<Option<i64>> = text
+        .iter()
+        .enumerate()
+        .map(|(j, &c)| (c == query[0]).then(|| MATCH + if word_start(j) { WORD_START } else { 0 }))
+        .collect();
+    for &q in &query[1..] {
+        let mut next = vec![None; text.len()];
+        // Max of best[k] + k * GAP_EXTEND over k <= j - 2, for gapped transitions into j.
+        let mut gapped: Option<i64> = None;
+        for j in 1..text.len() {
+            if j >= 2
+                && let Some(b) = best[j - 2]
+            {
+                let v = b + (j as i64 - 2) * GAP_EXTEND;
+                gapped = Some(gapped.map_or(v, |g| g.max(v)));
+            }
+            if text[j] != q {
+                continue;
+            }
+            let from_adjacent = best[j - 1].map(|b| b + CONSECUTIVE);
+            let from_gap = gapped.map(|g| g - (j as i64 - 1) * GAP_EXTEND - GAP_OPEN);
+            let prev = match (from_adjacent, from_gap) {
+                (Some(a), Some(g)) => Some(a.max(g)),
+                (a, g) => a.or(g),
+            };
+            next[j] = prev.map(|p| p + MATCH + if word_start(j) { WORD_START } else { 0 });
+        }
+        best = next;
+    }
+    best.into_iter().flatten().max()
+}
+
+/// Memories whose text matches `re`, newest first.
+pub fn grep_memories(memories: &[MemoryRecord], re: &Regex, limit: usize) -> Vec<MemoryRecord> {
     let mut rows: Vec<MemoryRecord> = memories
         .iter()
-        .filter(|m| m.text.to_lowercase().contains(&needle))
+        .filter(|m| re.is_match(&m.text))
         .cloned()
         .collect();
-    rows.sort_by(|a, b| b.ts_utc.cmp(&a.ts_utc));
-    rows.into_iter().take(limit).map(to_memory_row).collect()
+    rows.sort_by(newest_first);
+    rows.truncate(limit);
+    rows
+}
+
+/// Lowercase strings that count as a hit for `query` under `mode`.
+pub fn match_needles(query: &str, mode: MatchMode) -> Vec<String> {
+    match mode {
+        MatchMode::Phrase => vec![query.to_lowercase()],
+        MatchMode::All | MatchMode::Any => query_terms(query),
+    }
+}
+
+/// Byte ranges in `text` matching any lowercase needle case-insensitively.
+/// Overlapping matches are merged; ranges are sorted and always on char boundaries.
+pub fn match_ranges(text: &str, needles: &[String]) -> Vec<(usize, usize)> {
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for (start, _) in text.char_indices() {
+        for needle in needles.iter().filter(|n| !n.is_empty()) {
+            let mut lowered = String::new();
+            for (off, c) in text[start..].char_indices() {
+                lowered.extend(c.to_lowercase());
+                if lowered.len() >= needle.len() {
+                    if lowered == *needle {
+                        ranges.push((start, start + off + c.len_utf8()));
+                    }
+                    break;
+                }
+            }
+        }
+    }
+
+    ranges.sort();
+    let mut merged: Vec<(usize, usize)> = Vec::new();
+    for (start, end) in ranges {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+/// Distinct lowercase terms of a query, in first-seen order.
+pub fn query_terms(query: &str) -> Vec<String> {
+    let mut seen = HashSet::new();
+    query
+        .split_whitespace()
+        .map(str::to_lowercase)
+        .filter(|t| seen.insert(t.clone()))
+        .collect()
+}
+
+/// Number of `terms` (already lowercase) that occur in `text_lower`.
+pub fn coverage(text_lower: &str, terms: &[String]) -> usize {
+    terms
+        .iter()
+        .filter(|t| text_lower.contains(t.as_str()))
+        .count()
+}
+
+fn header_outdated(path: &Path, header: &str) -> Result<bool> {
+    if !path.exists() {
+        return Ok(false);
+    }
+    let contents = fs::read_to_string(path).with_context(|| format!("read {}", path.display()))?;
+    let first = contents.lines().next().unwrap_or_default();
+    Ok(!first.is_empty() && first != header.trim_end())
 }
 
 fn ensure_csv_file(path: &Path, header: &str) -> Result<()> {
@@ -202,16 +1583,71 @@ fn append_csv_row<T: Serialize>(path: &Path, row: &T) -> Result<()> {
     Ok(())
 }
 
-fn to_memory_row(rec: MemoryRecord) -> MemoryRow {
-    (
-        rec.id,
-        rec.kind,
-        rec.text,
-        rec.ts_utc,
-        rec.cwd,
-        rec.git_branch,
-        rec.git_head,
-    )
+fn rewrite_csv<T: Serialize>(path: &Path, header: &str, rows: &[T]) -> Result<()> {
+    let tmp_path = stage_csv(path, header, rows)?;
+    fs::rename(&tmp_path, path)
+        .with_context(|| format!("rename {} -> {}", tmp_path.display(), path.display()))?;
+    Ok(())
+}
+
+/// Replace the memories, handoffs, and links files together; a `None` file is left untouched.
+/// All temp files are written and synced before any is renamed into place, so a failure while
+/// writing (disk full, bad row) leaves the store exactly as it was.
+pub fn rewrite_store(
+    memories: Option<(&Path, &[MemoryRecord])>,
+    handoffs: Option<(&Path, &[HandoffRecord])>,
+    links: Option<(&Path, &[LinkRecord])>,
+) -> Result<()> {
+    let mut staged: Vec<(PathBuf, &Path)> = Vec::new();
+    let result = (|| {
+        if let Some((path, rows)) = memories {
+            staged.push((stage_csv(path, MEMORIES_HEADER, rows)?, path));
+        }
+        if let Some((path, rows)) = handoffs {
+            staged.push((stage_csv(path, HANDOFFS_HEADER, rows)?, path));
+        }
+        if let Some((path, rows)) = links {
+            staged.push((stage_csv(path, LINKS_HEADER, rows)?, path));
+        }
+        Ok(())
+    })();
+    if let Err(e) = result {
+        for (tmp, _) in &staged {
+            let _ = fs::remove_file(tmp);
+        }
+        return Err(e);
+    }
+    for (tmp, path) in &staged {
+        fs::rename(tmp, path)
+            .with_context(|| format!("rename {} -> {}", tmp.display(), path.display()))?;
+    }
+    Ok(())
+}
+
+/// Write `header` and `rows` to `<path>.tmp` and sync it, returning the temp path.
+fn stage_csv<T: Serialize>(path: &Path, header: &str, rows: &[T]) -> Result<PathBuf> {
+    let file_name = path
+        .file_name()
+        .with_context(|| format!("no file name in {}", path.display()))?;
+    let tmp_path = path.with_file_name(format!("{}.tmp", file_name.to_string_lossy()));
+
+    let mut file =
+        fs::File::create(&tmp_path).with_context(|| format!("create {}", tmp_path.display()))?;
+    file.write_all(header.as_bytes())
+        .with_context(|| format!("write {}", tmp_path.display()))?;
+
+    let mut writer = WriterBuilder::new().has_headers(false).from_writer(file);
+    for row in rows {
+        writer
+            .serialize(row)
+            .with_context(|| format!("write {}", tmp_path.display()))?;
+    }
+    let file = writer
+        .into_inner()
+        .map_err(|e| anyhow::anyhow!("flush {}: {}", tmp_path.display(), e.error()))?;
+    file.sync_all()
+        .with_context(|| format!("sync {}", tmp_path.display()))?;
+    Ok(tmp_path)
 }
 
 fn build_prefix_candidates(id_prefix: &str, canonical: &str, legacy: &str) -> Vec<String> {
@@ -223,7 +1659,405 @@ fn build_prefix_candidates(id_prefix: &str, canonical: &str, legacy: &str) -> Ve
     candidates
 }
 
+/// The first candidate (in the order tried) that is some memory's full id.
+fn exact_memory_id(memories: &[MemoryRecord], candidates: &[String]) -> Option<String> {
+    candidates.iter().find_map(|c| {
+        memories
+            .iter()
+            .find(|m| m.id.eq_ignore_ascii_case(c))
+            .map(|m| m.id.clone())
+    })
+}
+
 fn matches_any_prefix(id: &str, candidates: &[String]) -> bool {
     let id_lower = id.to_ascii_lowercase();
     candidates.iter().any(|p| id_lower.starts_with(p))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lock::StoreLock;
+    use crate::test_support::{TempDir, full_memory, handoff, ids, memories, memory};
+
+    #[test]
+    fn first_takes_oldest_in_ascending_order() {
+        let rows = memories(8);
+        assert_eq!(
+            ids(&first_memories(&rows, 5)),
+            ["cr-1", "cr-2", "cr-3", "cr-4", "cr-5"]
+        );
+        assert_eq!(
+            ids(&list_memories(&rows, 5)),
+            ["cr-8", "cr-7", "cr-6", "cr-5", "cr-4"]
+        );
+    }
+
+    #[test]
+    fn first_applies_after_filters() {
+        let mut rows = memories(6);
+        for m in rows.iter_mut().filter(|m| m.seq % 2 == 0) {
+            m.kind = Kind::Why;
+        }
+        let filter = MemoryFilter {
+            kind: Some(Kind::Why),
+            ..Default::default()
+        };
+        let why = filter_memories(&rows, &filter);
+        assert_eq!(ids(&first_memories(&why, 2)), ["cr-2", "cr-4"]);
+    }
+
+    fn with_text(id: &str, seq: u64, text: &str) -> MemoryRecord {
+        MemoryRecord {
+            text: text.to_string(),
+            ..memory(id, seq)
+        }
+    }
+
+    #[test]
+    fn coverage_counts_distinct_terms() {
+        let terms = query_terms("Lock lock CSV");
+        assert_eq!(terms, ["lock", "csv"]);
+        assert_eq!(coverage("csv lock file", &terms), 2);
+        assert_eq!(coverage("lock only", &terms), 1);
+        assert_eq!(coverage("neither", &terms), 0);
+    }
+
+    #[test]
+    fn coverage_sort_ranks_more_terms_first_then_newest() {
+        let rows = vec![
+            with_text("cr-1", 1, "lock and csv"),
+            with_text("cr-2", 2, "lock"),
+            with_text("cr-3", 3, "csv"),
+            with_text("cr-4", 4, "csv lock"),
+            with_text("cr-5", 5, "unrelated"),
+        ];
+        let opts = FindOptions {
+            mode: MatchMode::Any,
+            sort: FindSort::Coverage,
+            oldest: false,
+        };
+        let found = find_memories(&rows, "lock csv", opts, 10);
+        assert_eq!(ids(&found), ["cr-4", "cr-1", "cr-3", "cr-2"]);
+
+        let newest = FindOptions {
+            sort: FindSort::Newest,
+            ..opts
+        };
+        let found = find_memories(&rows, "lock csv", newest, 10);
+        assert_eq!(ids(&found), ["cr-4", "cr-3", "cr-2", "cr-1"]);
+    }
+
+    #[test]
+    fn handoff_slice_lower_bound_is_exclusive_unless_asked() {
+        let rows = memories(5);
+        let h = handoff("hf-1", Some("cr-2"), "cr-4");
+        assert_eq!(
+            ids(&handoff_slice_bounded(&rows, &h, false)),
+            ["cr-4", "cr-3"]
+        );
+        assert_eq!(
+            ids(&handoff_slice_bounded(&rows, &h, true)),
+            ["cr-4", "cr-3", "cr-2"]
+        );
+    }
+
+    #[test]
+    fn rm_latest_or_middle_memory_has_no_dependents() {
+        let rows = memories(5);
+        let handoffs = [handoff("hf-1", None, "cr-2")];
+        for id in ["cr-5", "cr-3"] {
+            let deps = dependents(&rows, &handoffs, id);
+            assert!(deps.is_empty());
+            deps.ensure_removable(id, false).unwrap();
+        }
+    }
+
+    #[test]
+    fn rm_handoff_boundary_needs_force() {
+        let mut rows = memories(5);
+        rows[3].parent_id = Some("cr-2".to_string());
+        let handoffs = [
+            handoff("hf-1", None, "cr-2"),
+            handoff("hf-2", Some("cr-2"), "cr-4"),
+        ];
+        let deps = dependents(&rows, &handoffs, "cr-2");
+        assert_eq!(deps.handoffs, ["hf-1", "hf-2"]);
+        assert_eq!(deps.children, ["cr-4"]);
+        let err = deps.ensure_removable("cr-2", false).unwrap_err();
+        assert!(err.to_string().contains("hf-1, hf-2"), "{err}");
+        deps.ensure_removable("cr-2", true).unwrap();
+    }
+
+    #[test]
+    fn csv_export_parses_back_losslessly() {
+        let rows = vec![full_memory("cr-1", 1), memory("cr-2", 2)];
+        let csv = memories_csv_string(&rows).unwrap();
+        assert_eq!(parse_memories(&csv).unwrap(), rows);
+    }
+
+    #[test]
+    fn concurrent_locked_appends_keep_ids_unique() {
+        let dir = TempDir::new("append-threads");
+        let path = dir.path().join("memories.csv");
+        ensure_memories_file(&path).unwrap();
+
+        let workers: Vec<_> = (0..2)
+            .map(|_| {
+                let (dir, path) = (dir.path().to_path_buf(), path.clone());
+                std::thread::spawn(move || {
+                    for _ in 0..50 {
+                        let _lock = StoreLock::exclusive(&dir).unwrap();
+                        let seq = next_seq(&read_memories(&path).unwrap());
+                        append_memory(&path, &memory(&format!("cr-{seq}"), seq)).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for w in workers {
+            w.join().unwrap();
+        }
+
+        let rows = read_memories(&path).unwrap();
+        let unique: HashSet<&str> = rows.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(rows.len(), 100);
+        assert_eq!(unique.len(), 100);
+    }
+
+    #[test]
+    fn handoff_slice_from_start_and_window() {
+        let rows = memories(5);
+        let start = handoff("hf-1", None, "cr-3");
+        assert_eq!(ids(&handoff_slice(&rows, &start)), ["cr-3", "cr-2", "cr-1"]);
+        let window = handoff("hf-2", Some("cr-3"), "cr-5");
+        assert_eq!(ids(&handoff_slice(&rows, &window)), ["cr-5", "cr-4"]);
+        let gone = handoff("hf-3", Some("cr-1"), "cr-9");
+        assert!(handoff_slice(&rows, &gone).is_empty());
+    }
+
+    #[test]
+    fn handoff_slice_splits_shared_timestamps_by_seq() {
+        let mut rows = memories(5);
+        for m in &mut rows {
+            m.ts_utc = "2024-01-01T00:00:00.000Z".to_string();
+        }
+        let h = handoff("hf-1", Some("cr-2"), "cr-4");
+        assert_eq!(ids(&handoff_slice(&rows, &h)), ["cr-4", "cr-3"]);
+    }
+
+    #[test]
+    fn sort_memories_by_each_key_breaks_ties_by_id() {
+        let rows = vec![
+            MemoryRecord {
+                kind: Kind::Why,
+                cwd: "b".to_string(),
+                ..with_text("cr-c", 1, "beta")
+            },
+            MemoryRecord {
+                cwd: "a".to_string(),
+                ..with_text("cr-a", 3, "alpha")
+            },
+            MemoryRecord {
+                cwd: "b".to_string(),
+                ..with_text("cr-b", 2, "alpha")
+            },
+        ];
+        let sorted = |key, desc| ids(&sort_memories(&rows, key, desc)).join(" ");
+        assert_eq!(sorted(SortKey::Ts, true), "cr-a cr-b cr-c");
+        assert_eq!(sorted(SortKey::Seq, false), "cr-c cr-b cr-a");
+        assert_eq!(sorted(SortKey::Kind, false), "cr-a cr-b cr-c");
+        assert_eq!(sorted(SortKey::Cwd, false), "cr-a cr-b cr-c");
+        assert_eq!(sorted(SortKey::Cwd, true), "cr-c cr-b cr-a");
+        assert_eq!(sorted(SortKey::Text, false), "cr-a cr-b cr-c");
+        assert_eq!(sorted(SortKey::Text, true), "cr-c cr-b cr-a");
+    }
+
+    #[test]
+    fn shared_timestamps_order_by_seq_then_id() {
+        let mut rows = memories(4);
+        for m in &mut rows {
+            m.ts_utc = "2024-01-01T00:00:00.000Z".to_string();
+        }
+        rows.swap(0, 2);
+        rows.push(memory("cr-0", 4));
+        let newest = ["cr-4", "cr-0", "cr-3", "cr-2", "cr-1"];
+        assert_eq!(ids(&list_memories(&rows, 10)), newest);
+        assert_eq!(latest_memory(&rows).unwrap().id, "cr-4");
+        let found = find_memories(&rows, "memory", FindOptions::default(), 10);
+        assert_eq!(ids(&found), newest);
+    }
+
+    #[test]
+    fn branch_filter_skips_other_branches_and_records_outside_git() {
+        let mut rows = memories(4);
+        for (m, branch) in rows
+            .iter_mut()
+            .zip([Some("feat"), Some("main"), None, Some("feat")])
+        {
+            m.git_branch = branch.map(str::to_string);
+        }
+        let filter = MemoryFilter {
+            branch: Some("feat".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(ids(&filter_memories(&rows, &filter)), ["cr-1", "cr-4"]);
+    }
+
+    #[test]
+    fn fuzzy_find_ranks_tight_in_order_matches_first() {
+        let rows = vec![
+            with_text("cr-1", 1, "handoff mark window"),
+            with_text("cr-2", 2, "hand off the marker"),
+            with_text("cr-3", 3, "mark handoff"),
+            with_text("cr-4", 4, "nothing relevant"),
+        ];
+        let found = fuzzy_find(&rows, "hmark", 10);
+        let scores: Vec<i64> = found.iter().map(|(s, _)| *s).collect();
+        assert!(scores.windows(2).all(|w| w[0] >= w[1]), "{scores:?}");
+        let found_ids: Vec<&str> = found.iter().map(|(_, m)| m.id.as_str()).collect();
+        assert_eq!(found_ids[0], "cr-1");
+        // "mark handoff" only has h after the m-a-r-k run, so it cannot match in order.
+        assert!(!found_ids.contains(&"cr-3"));
+        assert!(!found_ids.contains(&"cr-4"));
+    }
+
+    #[test]
+    fn fuzzy_score_requires_query_chars_in_order() {
+        assert!(fuzzy_score("abc", "ac").is_some());
+        assert!(fuzzy_score("abc", "ca").is_none());
+        assert!(fuzzy_score("abc", "   ").is_none());
+        assert!(fuzzy_score("abc", "abc") > fuzzy_score("a-b-c", "abc"));
+    }
+
+    #[test]
+    fn fuzzy_find_cuts_at_limit_and_breaks_ties_newest_first() {
+        let rows: Vec<MemoryRecord> = (1..=4)
+            .map(|seq| with_text(&format!("cr-{seq}"), seq, "same text"))
+            .collect();
+        let found = fuzzy_find(&rows, "same", 2);
+        let found_ids: Vec<&str> = found.iter().map(|(_, m)| m.id.as_str()).collect();
+        assert_eq!(found_ids, ["cr-4", "cr-3"]);
+        assert_eq!(found[0].0, found[1].0);
+    }
+
+    #[test]
+    fn first_handoff_caps_to_window_newest_memories() {
+        let rows = memories(5);
+        let from = next_handoff_from(&rows, None, 3);
+        assert_eq!(from.as_deref(), Some("cr-2"));
+        let h = handoff("hf-1", from.as_deref(), "cr-5");
+        assert_eq!(ids(&handoff_slice(&rows, &h)), ["cr-5", "cr-4", "cr-3"]);
+
+        assert_eq!(next_handoff_from(&rows, None, 5), None);
+        assert_eq!(next_handoff_from(&rows, None, 9), None);
+    }
+
+    #[test]
+    fn later_handoff_starts_where_the_previous_ended() {
+        let rows = memories(5);
+        let prev = handoff("hf-1", None, "cr-3");
+        assert_eq!(
+            next_handoff_from(&rows, Some(&prev), 1).as_deref(),
+            Some("cr-3")
+        );
+    }
+    #[test]
+    fn stats_of_an_empty_store_are_zero() {
+        let stats = compute_stats(&[], &[]);
+        assert_eq!(stats, Stats::default());
+    }
+
+    #[test]
+    fn stats_count_each_kind() {
+        let mut rows = memories(5);
+        rows[1].kind = Kind::Why;
+        rows[3].kind = Kind::Why;
+        rows[4].git_branch = Some("main".to_string());
+        let stats = compute_stats(&rows, &[handoff("hf-1", None, "cr-5")]);
+        assert_eq!(stats.memories, 5);
+        assert_eq!(stats.by_kind, [(Kind::What, 3), (Kind::Why, 2)]);
+        assert_eq!(stats.handoffs, 1);
+        assert_eq!(stats.oldest_ts.as_deref(), Some("2024-01-01T00:00:01.000Z"));
+        assert_eq!(stats.newest_ts.as_deref(), Some("2024-01-01T00:00:05.000Z"));
+        assert_eq!((stats.distinct_cwds, stats.distinct_branches), (1, 1));
+    }
+    #[test]
+    fn branch_stats_put_the_most_recent_branch_first() {
+        let mut rows = memories(5);
+        for (m, branch) in
+            rows.iter_mut()
+                .zip([Some("main"), None, Some("feat"), Some("main"), None])
+        {
+            m.git_branch = branch.map(str::to_string);
+        }
+        let stats = branch_stats(&rows);
+        let summary: Vec<(Option<&str>, usize, &str)> = stats
+            .iter()
+            .map(|b| (b.branch.as_deref(), b.count, b.latest_ts.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (None, 2, "2024-01-01T00:00:05.000Z"),
+                (Some("main"), 2, "2024-01-01T00:00:04.000Z"),
+                (Some("feat"), 1, "2024-01-01T00:00:03.000Z"),
+            ]
+        );
+    }
+    #[test]
+    fn thread_roots_count_descendants_and_adopt_orphans() {
+        let mut rows = memories(6);
+        for (m, parent) in rows.iter_mut().zip([
+            None,
+            Some("cr-1"),
+            Some("cr-2"),
+            Some("cr-1"),
+            Some("gone"),
+            None,
+        ]) {
+            m.parent_id = parent.map(str::to_string);
+        }
+        let roots = thread_roots(&rows);
+        assert_eq!(roots.len(), 3);
+        assert_eq!(roots["cr-1"], 3);
+        assert_eq!(roots["cr-5"], 0);
+        assert_eq!(roots["cr-6"], 0);
+    }
+    #[test]
+    fn neighbors_clip_the_window_at_either_end() {
+        let rows = memories(5);
+        let around = |id: &str, n: usize| {
+            let (before, at, after) = neighbors(&rows, id, n)?;
+            let before: Vec<&str> = before.into_iter().map(|m| m.id.as_str()).collect();
+            let after: Vec<&str> = after.into_iter().map(|m| m.id.as_str()).collect();
+            Some((before, at.id.as_str(), after))
+        };
+        assert_eq!(
+            around("cr-3", 1),
+            Some((vec!["cr-2"], "cr-3", vec!["cr-4"]))
+        );
+        assert_eq!(
+            around("cr-1", 2),
+            Some((vec![], "cr-1", vec!["cr-2", "cr-3"]))
+        );
+        assert_eq!(
+            around("cr-5", 2),
+            Some((vec!["cr-3", "cr-4"], "cr-5", vec![]))
+        );
+        assert_eq!(around("cr-9", 2), None);
+    }
+    #[test]
+    fn uncovered_memories_fall_outside_every_slice() {
+        let rows = memories(7);
+        let handoffs = [
+            handoff("hf-1", Some("cr-1"), "cr-3"),
+            handoff("hf-2", Some("cr-4"), "cr-5"),
+            handoff("hf-3", Some("cr-5"), "cr-gone"),
+        ];
+        assert_eq!(
+            ids(&uncovered_memories(&rows, &handoffs)),
+            ["cr-7", "cr-6", "cr-4", "cr-1"]
+        );
+        assert_eq!(ids(&uncovered_memories(&rows, &[])).len(), 7);
+    }
+}
@@ -0,0 +1,168 @@
+use crate::csv_store::MemoryRecord;
+use serde::Serialize;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProblemKind {
+    MissingCwd,
+    EmbeddedNewline,
+}
+
+impl ProblemKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ProblemKind::MissingCwd => "missing-cwd",
+            ProblemKind::EmbeddedNewline => "embedded-newline",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Problem {
+    #[serde(rename = "type")]
+    pub kind: ProblemKind,
+    pub id: String,
+    pub message: String,
+}
+
+/// Flag memories whose recorded `cwd` no longer exists relative to `root`.
+/// Relative values resolve against the store root; absolute ones are checked as-is.
+pub fn check_cwds(memories: &[MemoryRecord], root: &Path) -> Vec<Problem> {
+    memories
+        .iter()
+        .filter(|m| !root.join(&m.cwd).is_dir())
+        .map(|m| Problem {
+            kind: ProblemKind::MissingCwd,
+            id: m.id.clone(),
+            message: format!("cwd does not exist: {}", m.cwd),
+        })
+        .collect()
+}
+
+/// `(name, value)` for each field of `m` that must stay on one line in tab-separated output.
+fn line_fields(m: &MemoryRecord) -> [(&'static str, Option<&str>); 5] {
+    [
+        ("text", Some(m.text.as_str())),
+        ("cwd", Some(m.cwd.as_str())),
+        ("git_branch", m.git_branch.as_deref()),
+        ("git_head", m.git_head.as_deref()),
+        ("git_diffstat", m.git_diffstat.as_deref()),
+    ]
+}
+
+fn has_newline(s: &str) -> bool {
+    s.contains(['\n', '\r'])
+}
+
+/// Flag memories with `\n` or `\r` in a single-line field (one problem per field), typically
+/// from hand edits or stores written before newlines were rejected.
+pub fn find_malformed(memories: &[MemoryRecord]) -> Vec<Problem> {
+    memories
+        .iter()
+        .flat_map(|m| {
+            line_fields(m)
+                .into_iter()
+                .filter(|(_, v)| v.is_some_and(has_newline))
+                .map(|(field, _)| Problem {
+                    kind: ProblemKind::EmbeddedNewline,
+                    id: m.id.clone(),
+                    message: format!("newline in {field}"),
+                })
+        })
+        .collect()
+}
+
+/// Replace each `\r\n`, `\n`, or `\r` in single-line fields with a space, in place.
+/// Returns how many records changed; ids and order are untouched.
+pub fn fix_newlines(memories: &mut [MemoryRecord]) -> usize {
+    fn fix(s: &mut String) -> bool {
+        if !has_newline(s) {
+            return false;
+        }
+        *s = s.replace("\r\n", " ").replace(['\n', '\r'], " ");
+        true
+    }
+
+    let mut changed = 0;
+    for m in memories.iter_mut() {
+        let mut any = fix(&mut m.text);
+        any |= fix(&mut m.cwd);
+        for v in [&mut m.git_branch, &mut m.git_head, &mut m.git_diffstat]
+            .into_iter()
+            .flatten()
+        {
+            any |= fix(v);
+        }
+        if any {
+            changed += 1;
+        }
+    }
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{TempDir, full_memory, ids, memories};
+
+    #[test]
+    fn check_cwds_flags_missing_relative_and_absolute_dirs() {
+        let dir = TempDir::new("doctor-cwds");
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        let present = dir.path().join("sub").to_string_lossy().to_string();
+        let mut rows = memories(5);
+        for (m, cwd) in rows
+            .iter_mut()
+            .zip([".", "sub", "gone", &present, "/definitely/not/here"])
+        {
+            m.cwd = cwd.to_string();
+        }
+
+        let problems = check_cwds(&rows, dir.path());
+        let flagged: Vec<&str> = problems.iter().map(|p| p.id.as_str()).collect();
+        assert_eq!(flagged, ["cr-3", "cr-5"]);
+        assert!(problems.iter().all(|p| p.kind == ProblemKind::MissingCwd));
+    }
+
+    #[test]
+    fn find_malformed_names_each_field_with_a_newline() {
+        let mut rows = memories(3);
+        rows[1] = full_memory("cr-2", 2);
+        rows[1].text = "two\nlines".to_string();
+        rows[1].cwd = "sub\r".to_string();
+        rows[1].git_branch = Some("feat\n".to_string());
+        rows[1].git_head = Some("\rabc".to_string());
+        rows[1].git_diffstat = Some("1 file\r\n".to_string());
+
+        let problems = find_malformed(&rows);
+        let found: Vec<(&str, &str)> = problems
+            .iter()
+            .map(|p| (p.id.as_str(), p.message.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("cr-2", "newline in text"),
+                ("cr-2", "newline in cwd"),
+                ("cr-2", "newline in git_branch"),
+                ("cr-2", "newline in git_head"),
+                ("cr-2", "newline in git_diffstat"),
+            ]
+        );
+        assert!(find_malformed(&memories(3)).is_empty());
+    }
+
+    #[test]
+    fn fix_newlines_keeps_ids_and_order() {
+        let mut rows = memories(3);
+        rows[0].text = "a\r\nb".to_string();
+        rows[2].git_branch = Some("x\ny\rz".to_string());
+        assert_eq!(fix_newlines(&mut rows), 2);
+        assert_eq!(ids(&rows), ["cr-1", "cr-2", "cr-3"]);
+        assert_eq!(rows[0].text, "a b");
+        assert_eq!(rows[1].text, "memory cr-2");
+        assert_eq!(rows[2].git_branch.as_deref(), Some("x y z"));
+        assert!(find_malformed(&rows).is_empty());
+    }
+}
@@ -0,0 +1,309 @@
+use crate::csv_store::{HandoffRecord, Kind, LinkRecord, MemoryRecord, truncate_text};
+use crate::output::relative_age;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use std::collections::HashSet;
+
+const RECORDS_OPEN: &str = "{{#records}}";
+const RECORDS_CLOSE: &str = "{{/records}}";
+const DOT_LABEL_LEN: usize = 40;
+
+/// Replace every match of any of `patterns` in each memory's text with `[REDACTED]`.
+pub fn redact_text(records: &mut [MemoryRecord], patterns: &[Regex]) {
+    for r in records.iter_mut() {
+        for re in patterns {
+            if let std::borrow::Cow::Owned(text) = re.replace_all(&r.text, "[REDACTED]") {
+                r.text = text;
+            }
+        }
+    }
+}
+
+/// Render memories as a Markdown list, one bullet per memory.
+pub fn render_markdown(records: &[MemoryRecord]) -> String {
+    let mut out = String::from("# crumbs export\n\n");
+    for r in records {
+        out.push_str(&format!(
+            "- `{}` **{}** ({}): {}\n",
+            r.id, r.kind, r.ts_utc, r.text
+        ));
+    }
+    out
+}
+
+/// Render memories as a Graphviz DOT graph: one node per memory (id plus truncated text),
+/// solid edges from parent to child, dashed edges for links. Edges to memories that no longer
+/// exist are left out.
+pub fn render_dot(records: &[MemoryRecord], links: &[LinkRecord]) -> String {
+    let ids: HashSet<&str> = records.iter().map(|r| r.id.as_str()).collect();
+    let mut out = String::from("digraph crumbs {\n  node [shape=box];\n");
+    for r in records {
+        let label = format!("{}\n{}", r.id, truncate_text(&r.text, DOT_LABEL_LEN));
+        out.push_str(&format!(
+            "  \"{}\" [label=\"{}\"];\n",
+            dot_escape(&r.id),
+            dot_escape(&label)
+        ));
+    }
+    for r in records {
+        if let Some(parent) = r.parent_id.as_deref()
+            && ids.contains(parent)
+        {
+            out.push_str(&format!(
+                "  \"{}\" -> \"{}\";\n",
+                dot_escape(parent),
+                dot_escape(&r.id)
+            ));
+        }
+    }
+    for l in links {
+        if ids.contains(l.from_id.as_str()) && ids.contains(l.to_id.as_str()) {
+            out.push_str(&format!(
+                "  \"{}\" -> \"{}\" [style=dashed];\n",
+                dot_escape(&l.from_id),
+                dot_escape(&l.to_id)
+            ));
+        }
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// Escape a string for use inside a double-quoted DOT id or label.
+fn dot_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Render a handoff checkpoint as a standalone Markdown document for review: checkpoint
+/// metadata, the shown memories (newest first) with relative times, and a version footer.
+pub fn render_handoff_markdown(
+    handoff: &HandoffRecord,
+    shown: &[&MemoryRecord],
+    total: usize,
+    now: DateTime<Utc>,
+) -> String {
+    let mut out = format!("# Handoff `{}`\n\n", handoff.id);
+    out.push_str(&format!(
+        "- **Created:** {} ({})\n",
+        handoff.ts_utc,
+        relative_age(&handoff.ts_utc, now)
+    ));
+    out.push_str(&format!(
+        "- **Range:** `{}` → `{}`\n",
+        handoff.from_memory_id.as_deref().unwrap_or("<start>"),
+        handoff.to_memory_id
+    ));
+    if let Some(branch) = handoff.git_branch.as_deref() {
+        match handoff.git_head.as_deref() {
+            Some(head) => out.push_str(&format!("- **Branch:** `{branch}` @ `{head}`\n")),
+            None => out.push_str(&format!("- **Branch:** `{branch}`\n")),
+        }
+    }
+    if let Some(note) = handoff.note.as_deref() {
+        out.push_str(&format!("- **Note:** {note}\n"));
+    }
+    out.push_str(&format!("- **Window:** {}\n", handoff.suggested_window));
+    out.push_str(&format!("- **Memories:** {}/{total}\n", shown.len()));
+
+    out.push_str("\n## Memories (newest first)\n\n");
+    if shown.is_empty() {
+        out.push_str("_No memories in this checkpoint._\n");
+    }
+    for m in shown {
+        out.push_str(&format!(
+            "- `{}` **{}** · {}: {}\n",
+            m.id,
+            m.kind,
+            relative_age(&m.ts_utc, now),
+            m.text
+        ));
+    }
+
+    out.push_str(&format!(
+        "\n---\n_Generated by crumbs {} from handoff `{}`._\n",
+        env!("CARGO_PKG_VERSION"),
+        handoff.id
+    ));
+    out
+}
+
+/// Render a handoff's slice as a review digest: a header with the checkpoint id and window,
+/// then one section per kind (`what` before `why`), each bulleted newest first.
+pub fn render_handoff_digest(handoff: &HandoffRecord, slice: &[MemoryRecord]) -> String {
+    let mut out = format!("# Handoff `{}`\n\n", handoff.id);
+    out.push_str(&format!(
+        "- **Range:** `{}` → `{}`\n",
+        handoff.from_memory_id.as_deref().unwrap_or("<start>"),
+        handoff.to_memory_id
+    ));
+    out.push_str(&format!("- **Window:** {}\n", handoff.suggested_window));
+    if let Some(note) = handoff.note.as_deref() {
+        out.push_str(&format!("- **Note:** {note}\n"));
+    }
+
+    let mut kinds: Vec<Kind> = Vec::new();
+    for m in slice {
+        if !kinds.contains(&m.kind) {
+            kinds.push(m.kind);
+        }
+    }
+    kinds.sort();
+    if kinds.is_empty() {
+        out.push_str("\n_No memories in this checkpoint._\n");
+    }
+    for kind in kinds {
+        out.push_str(&format!("\n## {kind}\n\n"));
+        for m in slice.iter().filter(|m| m.kind == kind) {
+            out.push_str(&format!("- {} `{}`: {}\n", m.ts_utc, m.id, m.text));
+        }
+    }
+    out
+}
+
+/// A user template: `header {{#records}}body{{/records}} footer`.
+///
+/// The body repeats once per memory. Placeholders are `{{id}}`, `{{kind}}`, `{{text}}`,
+/// `{{ts_utc}}`, `{{cwd}}`, `{{git_branch}}`, `{{git_head}}`, `{{git_diffstat}}`,
+/// `{{parent_id}}`; header and footer may use `{{count}}`. Without a records block the whole template is the body.
+#[derive(Debug, Clone)]
+pub struct Template {
+    header: String,
+    body: String,
+    footer: String,
+}
+
+pub fn parse_template(src: &str) -> Result<Template> {
+    let Some(open) = src.find(RECORDS_OPEN) else {
+        if src.contains(RECORDS_CLOSE) {
+            anyhow::bail!("template has {RECORDS_CLOSE} without {RECORDS_OPEN}");
+        }
+        return Ok(Template {
+            header: String::new(),
+            body: src.to_string(),
+            footer: String::new(),
+        });
+    };
+    let rest = &src[open + RECORDS_OPEN.len()..];
+    let close = rest
+        .find(RECORDS_CLOSE)
+        .ok_or_else(|| anyhow::anyhow!("template has {RECORDS_OPEN} without {RECORDS_CLOSE}"))?;
+    Ok(Template {
+        header: src[..open].to_string(),
+        body: rest[..close].to_string(),
+        footer: rest[close + RECORDS_CLOSE.len()..].to_string(),
+    })
+}
+
+/// Render `records` through `template`. In `strict` mode unknown placeholders are an error;
+/// otherwise they are emitted verbatim.
+pub fn render_template(
+    template: &Template,
+    records: &[MemoryRecord],
+    strict: bool,
+) -> Result<String> {
+    let count = records.len().to_string();
+    let outer = |name: &str| (name == "count").then(|| count.clone());
+
+    let mut out = substitute(&template.header, outer, strict)?;
+    for r in records {
+        out.push_str(&substitute(
+            &template.body,
+            |name| record_field(r, name),
+            strict,
+        )?);
+    }
+    out.push_str(&substitute(&template.footer, outer, strict)?);
+    Ok(out)
+}
+
+fn record_field(r: &MemoryRecord, name: &str) -> Option<String> {
+    let value = match name {
+        "id" => r.id.clone(),
+        "kind" => r.kind.to_string(),
+        "text" => r.text.clone(),
+        "ts_utc" => r.ts_utc.clone(),
+        "cwd" => r.cwd.clone(),
+        "git_branch" => r.git_branch.clone().unwrap_or_default(),
+        "git_head" => r.git_head.clone().unwrap_or_default(),
+        "git_diffstat" => r.git_diffstat.clone().unwrap_or_default(),
+        "parent_id" => r.parent_id.clone().unwrap_or_default(),
+        _ => return None,
+    };
+    Some(value)
+}
+
+fn substitute(src: &str, lookup: impl Fn(&str) -> Option<String>, strict: bool) -> Result<String> {
+    let mut out = String::with_capacity(src.len());
+    let mut rest = src;
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("}}") else {
+            if strict {
+                anyhow::bail!("unterminated placeholder in template");
+            }
+            out.push_str(&rest[start..]);
+            return Ok(out);
+        };
+        let name = after[..end].trim();
+        match lookup(name) {
+            Some(value) => out.push_str(&value),
+            None if strict => anyhow::bail!("unknown placeholder '{{{{{name}}}}}' in template"),
+            None => out.push_str(&rest[start..start + 2 + end + 2]),
+        }
+        rest = &after[end + 2..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::memories;
+
+    #[test]
+    fn dot_escape_quotes_backslashes_and_newlines() {
+        assert_eq!(dot_escape(r#"say "hi""#), r#"say \"hi\""#);
+        assert_eq!(dot_escape(r"C:\tmp"), r"C:\\tmp");
+        assert_eq!(dot_escape("a\r\nb\nc"), r"a\nb\nc");
+    }
+
+    #[test]
+    fn dot_graph_of_a_small_linked_thread() {
+        let mut rows = memories(3);
+        rows[1].parent_id = Some("cr-1".to_string());
+        rows[2].parent_id = Some("cr-gone".to_string());
+        rows[2].text = r#"quote " and \ slash"#.to_string();
+        let link = |from: &str, to: &str| LinkRecord {
+            from_id: from.to_string(),
+            to_id: to.to_string(),
+            ts_utc: "2024-01-02T00:00:00.000Z".to_string(),
+        };
+        let links = [link("cr-1", "cr-3"), link("cr-3", "cr-gone")];
+        assert_eq!(
+            render_dot(&rows, &links),
+            concat!(
+                "digraph crumbs {\n",
+                "  node [shape=box];\n",
+                "  \"cr-1\" [label=\"cr-1\\nmemory cr-1\"];\n",
+                "  \"cr-2\" [label=\"cr-2\\nmemory cr-2\"];\n",
+                "  \"cr-3\" [label=\"cr-3\\nquote \\\" and \\\\ slash\"];\n",
+                "  \"cr-1\" -> \"cr-2\";\n",
+                "  \"cr-1\" -> \"cr-3\" [style=dashed];\n",
+                "}\n",
+            )
+        );
+    }
+}
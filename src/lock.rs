@@ -0,0 +1,118 @@
+use anyhow::{Context, Result};
+use std::fs::{File, OpenOptions};
+use std::path::Path;
+
+const LOCK_FILE: &str = ".lock";
+
+/// Advisory lock over a `.crumbs` directory.
+///
+/// Writers hold it exclusively for the duration of a read-modify-write sequence (e.g. pick a
+/// fresh id, then append) so concurrent `cr` processes cannot interleave. Readers hold it
+/// shared while loading the CSVs, so they see the store either before or after a write, never
+/// a half-appended row. Released when dropped.
+pub struct StoreLock {
+    _file: Option<File>,
+}
+
+impl StoreLock {
+    pub fn exclusive(store_dir: &Path) -> Result<StoreLock> {
+        let path = store_dir.join(LOCK_FILE);
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .with_context(|| format!("open {}", path.display()))?;
+        file.lock()
+            .with_context(|| format!("lock {}", path.display()))?;
+        Ok(StoreLock { _file: Some(file) })
+    }
+
+    /// Lock for readers: any number may hold it at once, but not while a writer does.
+    ///
+    /// Opens the lock file read-only so reads work on read-only stores. A store without one
+    /// (never written by this version) has no writer to wait for, so nothing is locked.
+    pub fn shared(store_dir: &Path) -> Result<StoreLock> {
+        let path = store_dir.join(LOCK_FILE);
+        let file = match File::open(&path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(StoreLock { _file: None });
+            }
+            Err(e) => return Err(e).with_context(|| format!("open {}", path.display())),
+        };
+        file.lock_shared()
+            .with_context(|| format!("lock {}", path.display()))?;
+        Ok(StoreLock { _file: Some(file) })
+    }
+}
+
+/// Create the lock file if missing, so later readers have something to lock.
+pub fn ensure_lock_file(store_dir: &Path) -> Result<()> {
+    let path = store_dir.join(LOCK_FILE);
+    OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)
+        .with_context(|| format!("create {}", path.display()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::csv_store;
+    use crate::test_support::{TempDir, memory};
+
+    #[test]
+    fn exclusive_excludes_readers_until_dropped() {
+        let dir = TempDir::new("lock-exclusive");
+        let lock = StoreLock::exclusive(dir.path()).unwrap();
+        let reader = File::open(dir.path().join(LOCK_FILE)).unwrap();
+        assert!(reader.try_lock_shared().is_err());
+        drop(lock);
+        reader.try_lock_shared().unwrap();
+    }
+
+    #[test]
+    fn shared_without_lock_file_locks_nothing() {
+        let dir = TempDir::new("lock-missing");
+        StoreLock::shared(dir.path()).unwrap();
+        assert!(!dir.path().join(LOCK_FILE).exists());
+    }
+
+    #[test]
+    fn readers_never_see_a_partial_row() {
+        let dir = TempDir::new("lock-interleave");
+        let path = dir.path().join("memories.csv");
+        csv_store::ensure_memories_file(&path).unwrap();
+        ensure_lock_file(dir.path()).unwrap();
+
+        let writer = {
+            let (dir, path) = (dir.path().to_path_buf(), path.clone());
+            std::thread::spawn(move || {
+                for seq in 1..=200 {
+                    let _lock = StoreLock::exclusive(&dir).unwrap();
+                    let mut rec = memory(&format!("cr-{seq}"), seq);
+                    rec.text = "x".repeat(4096);
+                    csv_store::append_memory(&path, &rec).unwrap();
+                }
+            })
+        };
+        let mut seen = 0;
+        loop {
+            let done = writer.is_finished();
+            let _lock = StoreLock::shared(dir.path()).unwrap();
+            let rows = csv_store::read_memories(&path).unwrap();
+            assert!(rows.len() >= seen);
+            assert!(rows.iter().all(|m| m.text.len() == 4096));
+            seen = rows.len();
+            if done {
+                break;
+            }
+        }
+        writer.join().unwrap();
+        assert_eq!(seen, 200);
+    }
+}
@@ -0,0 +1,461 @@
+use crate::csv_store::{FeedEntry, HandoffRecord, Kind, MemoryRecord};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+/// Version of the JSON record shape. Bump whenever fields are added, removed, or retyped.
+pub const JSON_SCHEMA: u32 = 5;
+
+/// Modifiers for the JSON shape of memory records.
+#[derive(Debug, Clone, Default)]
+pub struct JsonOptions {
+    /// Emit `ts_utc` as integer milliseconds since the Unix epoch.
+    pub epoch: bool,
+    /// Wrap records as `{"schema": N, "records": [...]}`.
+    pub envelope: bool,
+    /// Add a `text_marked` field with matches wrapped in a delimiter.
+    pub mark: Option<Marker>,
+    /// Cursor for the next page, reported in the envelope.
+    pub next_cursor: Option<String>,
+    /// Append an `age_ms` field measured against this instant.
+    pub age_now: Option<DateTime<Utc>>,
+}
+
+/// Lowercase needles to mark, and the string placed on both sides of each match.
+#[derive(Debug, Clone)]
+pub struct Marker {
+    pub needles: Vec<String>,
+    pub delim: String,
+}
+
+#[derive(Serialize)]
+struct Envelope<T: Serialize> {
+    schema: u32,
+    records: T,
+    next_cursor: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum JsonTs<'a> {
+    Rfc3339(&'a str),
+    Epoch(Option<i64>),
+}
+
+/// One memory in JSON output. Keys are always emitted, in this declaration order, with `null`
+/// for absent values: id, kind, text, ts_utc, cwd, git_branch, git_head, git_diffstat,
+/// text_marked, parent_id, seq, tags, note. New keys are only ever appended, with a
+/// [`JSON_SCHEMA`] bump.
+/// The opt-in `age_ms` key is the exception: it only appears when requested.
+#[derive(Serialize)]
+struct JsonMemory<'a> {
+    id: &'a str,
+    kind: &'a str,
+    text: &'a str,
+    ts_utc: JsonTs<'a>,
+    cwd: &'a str,
+    git_branch: Option<&'a str>,
+    git_head: Option<&'a str>,
+    git_diffstat: Option<&'a str>,
+    text_marked: Option<String>,
+    parent_id: Option<&'a str>,
+    seq: u64,
+    tags: &'a str,
+    note: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    age_ms: Option<Option<i64>>,
+}
+
+#[derive(Serialize)]
+struct FindCount<'a> {
+    query: &'a str,
+    matches: usize,
+}
+
+/// `{"query": ..., "matches": N}` for `cr find --count --json`.
+pub fn find_count_json(query: &str, matches: usize) -> Result<String> {
+    serde_json::to_string(&FindCount { query, matches }).context("serialize json")
+}
+
+pub fn memories_json(records: &[MemoryRecord], opts: &JsonOptions) -> Result<String> {
+    let out: Vec<JsonMemory> = records.iter().map(|r| json_memory(r, opts)).collect();
+    if opts.envelope {
+        let env = Envelope {
+            schema: JSON_SCHEMA,
+            records: out,
+            next_cursor: opts.next_cursor.clone(),
+        };
+        return serde_json::to_string_pretty(&env).context("serialize json");
+    }
+    serde_json::to_string_pretty(&out).context("serialize json")
+}
+
+/// Newline-delimited JSON: one compact object per memory, same keys as [`memories_json`].
+/// No envelope; an empty input yields an empty string.
+pub fn memories_json_lines(records: &[MemoryRecord], opts: &JsonOptions) -> Result<String> {
+    let mut out = String::new();
+    for rec in records {
+        out.push_str(&serde_json::to_string(&json_memory(rec, opts)).context("serialize json")?);
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum JsonFeedEntry<'a> {
+    Memory(Box<JsonMemory<'a>>),
+    Handoff(&'a HandoffRecord),
+}
+
+/// The `recent` timeline as a JSON array; memory entries have the same keys as
+/// [`memories_json`], after a leading `"type": "memory"` (or `"handoff"`).
+pub fn feed_json(feed: &[FeedEntry]) -> Result<String> {
+    let opts = JsonOptions::default();
+    let out: Vec<JsonFeedEntry> = feed
+        .iter()
+        .map(|e| match e {
+            FeedEntry::Memory(m) => JsonFeedEntry::Memory(Box::new(json_memory(m, &opts))),
+            FeedEntry::Handoff(h) => JsonFeedEntry::Handoff(h),
+        })
+        .collect();
+    serde_json::to_string_pretty(&out).context("serialize json")
+}
+
+#[derive(Serialize)]
+struct JsonMemoryDetail<'a> {
+    #[serde(flatten)]
+    memory: JsonMemory<'a>,
+    handoffs: &'a [String],
+    links: &'a [String],
+}
+
+/// A single memory as a pretty-printed JSON object, with the same keys as [`memories_json`]
+/// plus trailing `handoffs` (ids of the checkpoints covering it) and `links` (ids of the
+/// memories linked to it) arrays.
+pub fn memory_json_detail(
+    record: &MemoryRecord,
+    opts: &JsonOptions,
+    handoffs: &[String],
+    links: &[String],
+) -> Result<String> {
+    let out = JsonMemoryDetail {
+        memory: json_memory(record, opts),
+        handoffs,
+        links,
+    };
+    serde_json::to_string_pretty(&out).context("serialize json")
+}
+
+fn json_memory<'a>(rec: &'a MemoryRecord, opts: &JsonOptions) -> JsonMemory<'a> {
+    let ts_utc = if opts.epoch {
+        JsonTs::Epoch(epoch_millis(&rec.ts_utc))
+    } else {
+        JsonTs::Rfc3339(&rec.ts_utc)
+    };
+    JsonMemory {
+        id: &rec.id,
+        kind: rec.kind.as_str(),
+        text: &rec.text,
+        ts_utc,
+        cwd: &rec.cwd,
+        git_branch: rec.git_branch.as_deref(),
+        git_head: rec.git_head.as_deref(),
+        git_diffstat: rec.git_diffstat.as_deref(),
+        text_marked: opts
+            .mark
+            .as_ref()
+            .map(|m| mark_text(&rec.text, &m.needles, &m.delim)),
+        parent_id: rec.parent_id.as_deref(),
+        seq: rec.seq,
+        tags: &rec.tags,
+        note: rec.note.as_deref(),
+        age_ms: opts
+            .age_now
+            .map(|now| epoch_millis(&rec.ts_utc).map(|ms| now.timestamp_millis() - ms)),
+    }
+}
+
+/// Wrap every case-insensitive occurrence of `needles` in `text` with `delim` on both sides.
+pub fn mark_text(text: &str, needles: &[String], delim: &str) -> String {
+    wrap_matches(text, needles, delim, delim)
+}
+
+/// Like [`mark_text`], but wraps matches in ANSI bold red for terminal output.
+pub fn highlight_text(text: &str, needles: &[String]) -> String {
+    wrap_matches(text, needles, "\x1b[1;31m", "\x1b[0m")
+}
+
+fn wrap_matches(text: &str, needles: &[String], open: &str, close: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut pos = 0;
+    for (start, end) in crate::csv_store::match_ranges(text, needles) {
+        out.push_str(&text[pos..start]);
+        out.push_str(open);
+        out.push_str(&text[start..end]);
+        out.push_str(close);
+        pos = end;
+    }
+    out.push_str(&text[pos..]);
+    out
+}
+
+/// When to emit ANSI colors, from the global `--color` option.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorChoice {
+    /// Color when stdout is a terminal and `NO_COLOR` is unset
+    #[default]
+    Auto,
+    /// Always color, even when piped or `NO_COLOR` is set
+    Always,
+    /// Never color
+    Never,
+}
+
+impl ColorChoice {
+    /// Whether to color, given if stdout is a terminal and whether `NO_COLOR` is set (non-empty).
+    pub fn enabled(self, is_terminal: bool, no_color_env: bool) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => is_terminal && !no_color_env,
+        }
+    }
+}
+
+/// Rows as space-padded columns (id, kind, ts, cwd, text) for reading in a terminal. With
+/// `color`, `what` and `why` kinds are tinted differently; padding ignores the escape codes.
+pub fn render_table(rows: &[MemoryRecord], color: bool) -> String {
+    let width = |f: fn(&MemoryRecord) -> &str| {
+        rows.iter()
+            .map(|r| crate::csv_store::text_len(f(r)))
+            .max()
+            .unwrap_or(0)
+    };
+    let (id_w, kind_w, ts_w, cwd_w) = (
+        width(|r| &r.id),
+        width(|r| r.kind.as_str()),
+        width(|r| &r.ts_utc),
+        width(|r| &r.cwd),
+    );
+    let pad = |s: &str, w: usize| {
+        let fill = w.saturating_sub(crate::csv_store::text_len(s));
+        format!("{s}{}", " ".repeat(fill))
+    };
+
+    let mut out = String::new();
+    for r in rows {
+        let kind = pad(r.kind.as_str(), kind_w);
+        let kind = match (color, r.kind) {
+            (false, _) => kind,
+            (true, Kind::What) => format!("\x1b[32m{kind}\x1b[0m"),
+            (true, Kind::Why) => format!("\x1b[33m{kind}\x1b[0m"),
+        };
+        out.push_str(&format!(
+            "{}  {kind}  {}  {}  {}\n",
+            pad(&r.id, id_w),
+            pad(&r.ts_utc, ts_w),
+            pad(&r.cwd, cwd_w),
+            r.text
+        ));
+    }
+    out
+}
+
+/// Coarse age of `ts_utc` relative to `now`, e.g. "just now", "5m ago", "3h ago", "2d ago".
+/// Unparseable timestamps are returned as-is.
+pub fn relative_age(ts_utc: &str, now: DateTime<Utc>) -> String {
+    let Ok(ts) = DateTime::parse_from_rfc3339(ts_utc) else {
+        return ts_utc.to_string();
+    };
+    let secs = (now - ts.with_timezone(&Utc)).num_seconds();
+    match secs {
+        s if s < 60 => "just now".to_string(),
+        s if s < 3600 => format!("{}m ago", s / 60),
+        s if s < 86_400 => format!("{}h ago", s / 3600),
+        s => format!("{}d ago", s / 86_400),
+    }
+}
+
+fn epoch_millis(ts_utc: &str) -> Option<i64> {
+    DateTime::parse_from_rfc3339(ts_utc)
+        .ok()
+        .map(|dt| dt.timestamp_millis())
+}
+
+/// Field used to bucket records under headers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupKey {
+    Branch,
+    Day,
+    Kind,
+}
+
+impl GroupKey {
+    fn label(self, rec: &MemoryRecord) -> &str {
+        match self {
+            GroupKey::Branch => rec.git_branch.as_deref().unwrap_or("(no branch)"),
+            GroupKey::Day => rec.ts_utc.get(..10).unwrap_or(&rec.ts_utc),
+            GroupKey::Kind => rec.kind.as_str(),
+        }
+    }
+}
+
+/// Bucket records by `key`. Groups appear in order of their first record, and records keep
+/// their relative order within a group.
+pub fn group_by_key(records: &[MemoryRecord], key: GroupKey) -> Vec<(&str, Vec<&MemoryRecord>)> {
+    let mut groups: Vec<(&str, Vec<&MemoryRecord>)> = Vec::new();
+    for rec in records {
+        let label = key.label(rec);
+        match groups.iter_mut().find(|(l, _)| *l == label) {
+            Some((_, group)) => group.push(rec),
+            None => groups.push((label, vec![rec])),
+        }
+    }
+    groups
+}
+
+/// Group consecutive records sharing a `cwd`, preserving order.
+/// Non-adjacent runs from the same directory produce separate groups.
+pub fn group_by_cwd(records: &[MemoryRecord]) -> Vec<(&str, Vec<&MemoryRecord>)> {
+    let mut groups: Vec<(&str, Vec<&MemoryRecord>)> = Vec::new();
+    for rec in records {
+        match groups.last_mut() {
+            Some((cwd, group)) if *cwd == rec.cwd => group.push(rec),
+            _ => groups.push((&rec.cwd, vec![rec])),
+        }
+    }
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::csv_store;
+    use crate::test_support::{TempDir, full_memory, memories, memory};
+
+    fn group_ids<'a>(
+        groups: Vec<(&'a str, Vec<&'a MemoryRecord>)>,
+    ) -> Vec<(&'a str, Vec<&'a str>)> {
+        groups
+            .into_iter()
+            .map(|(label, group)| (label, group.iter().map(|m| m.id.as_str()).collect()))
+            .collect()
+    }
+
+    #[test]
+    fn group_by_branch_keeps_first_seen_order() {
+        let mut rows = memories(5);
+        for (m, branch) in
+            rows.iter_mut()
+                .zip([Some("main"), Some("feat"), Some("main"), None, Some("feat")])
+        {
+            m.git_branch = branch.map(str::to_string);
+        }
+        assert_eq!(
+            group_ids(group_by_key(&rows, GroupKey::Branch)),
+            [
+                ("main", vec!["cr-1", "cr-3"]),
+                ("feat", vec!["cr-2", "cr-5"]),
+                ("(no branch)", vec!["cr-4"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn json_keys_are_complete_and_in_documented_order() {
+        let line = memories_json_lines(&[memory("cr-1", 1)], &JsonOptions::default()).unwrap();
+        assert_eq!(
+            line.trim_end(),
+            concat!(
+                r#"{"id":"cr-1","kind":"what","text":"memory cr-1","#,
+                r#""ts_utc":"2024-01-01T00:00:01.000Z","cwd":".","git_branch":null,"#,
+                r#""git_head":null,"git_diffstat":null,"text_marked":null,"parent_id":null,"#,
+                r#""seq":1,"tags":"","note":null}"#
+            )
+        );
+    }
+
+    #[test]
+    fn json_lines_each_parse_back_into_a_record() {
+        let rows = vec![full_memory("cr-1", 1), memory("cr-2", 2)];
+        let out = memories_json_lines(&rows, &JsonOptions::default()).unwrap();
+        let parsed: Vec<MemoryRecord> = out
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(parsed, rows);
+    }
+
+    #[test]
+    fn json_export_round_trips_into_a_fresh_store() {
+        let dir = TempDir::new("json-round-trip");
+        let (src, dst) = (dir.path().join("src.csv"), dir.path().join("dst.csv"));
+        let mut rows = memories(3);
+        rows.push(full_memory("cr-4", 4));
+        csv_store::rewrite_memories(&src, &rows).unwrap();
+
+        let exported = memories_json(
+            &csv_store::read_memories(&src).unwrap(),
+            &JsonOptions::default(),
+        );
+        let imported = csv_store::parse_memories(&exported.unwrap()).unwrap();
+        csv_store::rewrite_memories(&dst, &imported).unwrap();
+        assert_eq!(csv_store::read_memories(&dst).unwrap(), rows);
+    }
+
+    #[test]
+    fn color_choice_honors_no_color_unless_always() {
+        assert!(ColorChoice::Auto.enabled(true, false));
+        assert!(!ColorChoice::Auto.enabled(true, true));
+        assert!(!ColorChoice::Auto.enabled(false, false));
+        assert!(ColorChoice::Always.enabled(false, true));
+        assert!(!ColorChoice::Never.enabled(true, false));
+    }
+
+    #[test]
+    fn table_has_escapes_only_when_colored() {
+        let rows = vec![memory("cr-1", 1), full_memory("cr-22", 2)];
+        let plain = render_table(&rows, false);
+        let colored = render_table(&rows, true);
+        assert!(!plain.contains('\x1b'));
+        assert!(colored.contains("\x1b[32mwhat\x1b[0m"));
+        assert!(colored.contains("\x1b[33mwhy \x1b[0m"));
+        let strip = |s: &str| {
+            s.replace("\x1b[32m", "")
+                .replace("\x1b[33m", "")
+                .replace("\x1b[0m", "")
+        };
+        assert_eq!(strip(&colored), plain);
+    }
+
+    #[test]
+    fn groups_keep_newest_first_order_within_and_across() {
+        let mut rows = memories(5);
+        for (m, cwd) in rows.iter_mut().zip(["a", "a", "b", "a", "a"]) {
+            m.cwd = cwd.to_string();
+        }
+        let newest = csv_store::list_memories(&rows, 10);
+        assert_eq!(
+            group_ids(group_by_cwd(&newest)),
+            [
+                ("a", vec!["cr-5", "cr-4"]),
+                ("b", vec!["cr-3"]),
+                ("a", vec!["cr-2", "cr-1"]),
+            ]
+        );
+        assert_eq!(
+            group_ids(group_by_key(&newest, GroupKey::Day)),
+            [("2024-01-01", vec!["cr-5", "cr-4", "cr-3", "cr-2", "cr-1"])]
+        );
+        rows[2].kind = Kind::Why;
+        let newest = csv_store::list_memories(&rows, 10);
+        assert_eq!(
+            group_ids(group_by_key(&newest, GroupKey::Kind)),
+            [
+                ("what", vec!["cr-5", "cr-4", "cr-2", "cr-1"]),
+                ("why", vec!["cr-3"]),
+            ]
+        );
+    }
+}
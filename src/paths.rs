@@ -41,3 +41,79 @@ pub fn store_root_from_cwd(cwd: &Path) -> PathBuf {
     }
     cwd.to_path_buf()
 }
+
+/// Rewrite an absolute stored `cwd` that lives under `old` so it lives under `new`.
+/// Returns `None` for relative values and paths outside `old`.
+pub fn relocate_cwd(cwd: &str, old: &Path, new: &Path) -> Option<String> {
+    let cwd = Path::new(cwd);
+    if !cwd.is_absolute() {
+        return None;
+    }
+    let rest = cwd.strip_prefix(old).ok()?;
+    let moved = if rest.as_os_str().is_empty() {
+        new.to_path_buf()
+    } else {
+        new.join(rest)
+    };
+    Some(moved.to_string_lossy().to_string())
+}
+
+/// Absolute form of a stored `cwd` (relative values are joined onto the store root).
+pub fn absolute_cwd(root: &Path, cwd: &str) -> String {
+    let path = Path::new(cwd);
+    if path.is_absolute() {
+        return cwd.to_string();
+    }
+    if cwd == "." {
+        return root.to_string_lossy().to_string();
+    }
+    root.join(path).to_string_lossy().to_string()
+}
+
+/// Ensure `<repo_root>/.gitignore` ignores `.crumbs/`. Returns `true` if the entry was added.
+pub fn ensure_gitignore_entry(repo_root: &Path) -> std::io::Result<bool> {
+    let path = repo_root.join(".gitignore");
+    let existing = match std::fs::read_to_string(&path) {
+        Ok(s) => s,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e),
+    };
+
+    let present = existing
+        .lines()
+        .map(str::trim)
+        .any(|l| matches!(l, ".crumbs" | ".crumbs/" | "/.crumbs" | "/.crumbs/"));
+    if present {
+        return Ok(false);
+    }
+
+    let mut contents = existing;
+    if !contents.is_empty() && !contents.ends_with('\n') {
+        contents.push('\n');
+    }
+    contents.push_str(".crumbs/\n");
+    std::fs::write(&path, contents)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relocate_matches_whole_path_components() {
+        let (old, new) = (Path::new("/old/proj"), Path::new("/new/proj"));
+        assert_eq!(
+            relocate_cwd("/old/proj", old, new).as_deref(),
+            Some("/new/proj")
+        );
+        assert_eq!(
+            relocate_cwd("/old/proj/sub", old, new).as_deref(),
+            Some("/new/proj/sub")
+        );
+        assert_eq!(relocate_cwd("/old/projX", old, new), None);
+        assert_eq!(relocate_cwd("/elsewhere", old, new), None);
+        assert_eq!(relocate_cwd("sub", old, new), None);
+        assert_eq!(relocate_cwd(".", old, new), None);
+    }
+}
@@ -0,0 +1,129 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+const QUERIES_FILE: &str = "queries.toml";
+const NAME_MAX_LEN: usize = 64;
+
+/// Saved `find` searches in `.crumbs/queries.toml`, keyed by name. A missing file is empty.
+/// Each entry is parsed on its own, so one bad entry (an unknown key, say) is reported by
+/// name without hiding the others.
+pub fn load<T: DeserializeOwned>(store_dir: &Path) -> Result<BTreeMap<String, Result<T>>> {
+    let path = store_dir.join(QUERIES_FILE);
+    Ok(read_table(&path)?
+        .into_iter()
+        .map(|(name, value)| {
+            let query = value
+                .try_into()
+                .with_context(|| format!("saved query '{name}' in {}", path.display()));
+            (name, query)
+        })
+        .collect())
+}
+
+/// Add or replace the entry `name`, leaving every other entry (valid or not) as written.
+/// The file is replaced via a temp file and rename, so readers never see a partial write.
+pub fn save<T: Serialize>(store_dir: &Path, name: &str, query: &T) -> Result<()> {
+    let path = store_dir.join(QUERIES_FILE);
+    let mut table = read_table(&path)?;
+    table.insert(
+        name.to_string(),
+        toml::Value::try_from(query).context("serialize saved query")?,
+    );
+    let tmp_path = store_dir.join(format!("{QUERIES_FILE}.tmp"));
+    let src = toml::to_string(&table).context("serialize saved queries")?;
+    std::fs::write(&tmp_path, src).with_context(|| format!("write {}", tmp_path.display()))?;
+    std::fs::rename(&tmp_path, &path)
+        .with_context(|| format!("rename {} -> {}", tmp_path.display(), path.display()))
+}
+
+fn read_table(path: &Path) -> Result<toml::Table> {
+    let src = match std::fs::read_to_string(path) {
+        Ok(s) => s,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(toml::Table::new()),
+        Err(e) => return Err(e).with_context(|| format!("read {}", path.display())),
+    };
+    toml::from_str(&src).with_context(|| format!("parse {}", path.display()))
+}
+
+/// Names are 1-64 ASCII letters, digits, `-`, or `_`, so they stay valid bare TOML keys.
+pub fn validate_name(name: &str) -> Result<()> {
+    if name.is_empty() || name.len() > NAME_MAX_LEN {
+        anyhow::bail!("query name must be 1-{NAME_MAX_LEN} characters");
+    }
+    if let Some(c) = name
+        .chars()
+        .find(|c| !c.is_ascii_alphanumeric() && *c != '-' && *c != '_')
+    {
+        anyhow::bail!("query name contains invalid character {c:?} (use letters, digits, - or _)");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+    use serde::Deserialize;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct Query {
+        query: String,
+        #[serde(default)]
+        oldest: bool,
+    }
+
+    fn query(text: &str) -> Query {
+        Query {
+            query: text.to_string(),
+            oldest: false,
+        }
+    }
+
+    #[test]
+    fn save_then_load_round_trips() {
+        let dir = TempDir::new("queries-round-trip");
+        assert!(load::<Query>(dir.path()).unwrap().is_empty());
+        validate_name("lock-bugs").unwrap();
+        save(dir.path(), "lock-bugs", &query("lock")).unwrap();
+        save(dir.path(), "csv_1", &query("csv")).unwrap();
+        save(dir.path(), "lock-bugs", &query("flock")).unwrap();
+
+        let loaded = load::<Query>(dir.path()).unwrap();
+        let names: Vec<&str> = loaded.keys().map(String::as_str).collect();
+        assert_eq!(names, ["csv_1", "lock-bugs"]);
+        assert_eq!(*loaded["lock-bugs"].as_ref().unwrap(), query("flock"));
+    }
+
+    #[test]
+    fn bad_entry_is_reported_by_name_and_others_still_load() {
+        let dir = TempDir::new("queries-bad-entry");
+        std::fs::write(
+            dir.path().join(QUERIES_FILE),
+            "[q1]\nquery = \"lock\"\n\n[q2]\nquery = \"csv\"\nbogus = 1\n",
+        )
+        .unwrap();
+        let loaded = load::<Query>(dir.path()).unwrap();
+        assert_eq!(*loaded["q1"].as_ref().unwrap(), query("lock"));
+        let err = loaded["q2"].as_ref().unwrap_err();
+        assert!(format!("{err:#}").contains("saved query 'q2'"), "{err:#}");
+
+        save(dir.path(), "q3", &query("new")).unwrap();
+        let loaded = load::<Query>(dir.path()).unwrap();
+        assert!(loaded["q2"].is_err());
+        assert!(loaded["q3"].is_ok());
+    }
+
+    #[test]
+    fn names_must_be_bare_toml_keys() {
+        validate_name("a").unwrap();
+        validate_name(&"x".repeat(64)).unwrap();
+        assert!(validate_name("").is_err());
+        assert!(validate_name(&"x".repeat(65)).is_err());
+        assert!(validate_name("has space").is_err());
+        assert!(validate_name("dotted.name").is_err());
+    }
+}
@@ -0,0 +1,87 @@
+//! Fixtures shared by the unit tests.
+
+use crate::csv_store::{HandoffRecord, Kind, MemoryRecord};
+use std::path::{Path, PathBuf};
+
+/// A `what` memory appended at `seq`, recorded `seq` seconds after 2024-01-01T00:00:00Z so
+/// time order and append order agree. Tests override fields with struct update syntax.
+pub fn memory(id: &str, seq: u64) -> MemoryRecord {
+    MemoryRecord {
+        id: id.to_string(),
+        kind: Kind::What,
+        text: format!("memory {id}"),
+        ts_utc: format!("2024-01-01T00:{:02}:{:02}.000Z", seq / 60, seq % 60),
+        cwd: ".".to_string(),
+        git_branch: None,
+        git_head: None,
+        git_diffstat: None,
+        parent_id: None,
+        seq,
+        tags: String::new(),
+        note: None,
+    }
+}
+
+/// A memory with every optional field set, for round-trip tests.
+pub fn full_memory(id: &str, seq: u64) -> MemoryRecord {
+    MemoryRecord {
+        kind: Kind::Why,
+        text: "quotes \" and, commas".to_string(),
+        git_branch: Some("main".to_string()),
+        git_head: Some("abc1234".to_string()),
+        git_diffstat: Some("2 files, +3 -1".to_string()),
+        parent_id: Some("cr-0".to_string()),
+        tags: "lock,csv".to_string(),
+        note: Some(format!("notes/{id}.md")),
+        ..memory(id, seq)
+    }
+}
+
+/// A handoff covering `from` (exclusive, `None` for `<start>`) up to `to`.
+pub fn handoff(id: &str, from: Option<&str>, to: &str) -> HandoffRecord {
+    HandoffRecord {
+        id: id.to_string(),
+        ts_utc: "2024-01-02T00:00:00.000Z".to_string(),
+        from_memory_id: from.map(str::to_string),
+        to_memory_id: to.to_string(),
+        suggested_window: 10,
+        cwd: ".".to_string(),
+        git_branch: None,
+        git_head: None,
+        note: None,
+    }
+}
+
+/// `n` memories `cr-1` ..= `cr-n`, oldest first.
+pub fn memories(n: u64) -> Vec<MemoryRecord> {
+    (1..=n)
+        .map(|seq| memory(&format!("cr-{seq}"), seq))
+        .collect()
+}
+
+pub fn ids(rows: &[MemoryRecord]) -> Vec<&str> {
+    rows.iter().map(|m| m.id.as_str()).collect()
+}
+
+/// A fresh directory under the system temp dir, removed on drop. `name` must be unique per
+/// test, since tests run in parallel.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(name: &str) -> TempDir {
+        let path = std::env::temp_dir().join(format!("crumbs-test-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).expect("create temp dir");
+        TempDir(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}